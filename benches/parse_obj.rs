@@ -0,0 +1,81 @@
+// times the parsing of a generated obj file, a grid of quads with texture coordinates and
+// normals, on one thread and on every core. The side of the grid may be given:
+//
+//     cargo bench --bench parse_obj -- 1000
+use scop::object_loader::{object::ParseOptions, Object};
+use std::{env, fmt::Write, thread, time::Instant};
+
+// the best of these runs is kept
+const RUNS: usize = 5;
+
+fn main() {
+    let side = env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1000);
+    let obj = grid(side);
+    let options = ParseOptions::default();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    println!(
+        "{side}x{side} grid: {} MB, {} vertices, {} faces",
+        obj.len() >> 20,
+        (side + 1) * (side + 1),
+        side * side
+    );
+
+    time("parse_reader", obj.len(), || {
+        Object::parse_reader(obj.as_bytes(), &options, |_| {}).unwrap()
+    });
+    time(
+        &format!("parse_parallel, {threads} threads"),
+        obj.len(),
+        || Object::parse_parallel(obj.as_bytes(), &options, threads, |_| {}).unwrap(),
+    );
+}
+
+fn time<T>(name: &str, size: usize, mut parse: impl FnMut() -> T) {
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(parse());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    println!(
+        "{name}: {:.1} ms, {:.0} MB/s",
+        best.as_secs_f64() * 1000.0,
+        size as f64 / best.as_secs_f64() / (1 << 20) as f64
+    );
+}
+
+// a wavy grid of `side`x`side` quads, with a normal per vertex
+fn grid(side: usize) -> String {
+    let mut obj = String::from("o grid\n");
+    let points = side + 1;
+    for y in 0..points {
+        for x in 0..points {
+            let (u, v) = (x as f32 / side as f32, y as f32 / side as f32);
+            let height = (u * 20.0).sin() * (v * 20.0).cos() * 0.05;
+            writeln!(obj, "v {u:.6} {height:.6} {v:.6}").unwrap();
+            writeln!(obj, "vt {u:.6} {v:.6}").unwrap();
+            writeln!(obj, "vn 0.000000 1.000000 0.000000").unwrap();
+        }
+    }
+    for y in 0..side {
+        for x in 0..side {
+            let corners = [
+                y * points + x + 1,
+                y * points + x + 2,
+                (y + 1) * points + x + 2,
+                (y + 1) * points + x + 1,
+            ];
+            obj.push('f');
+            for corner in corners {
+                write!(obj, " {corner}/{corner}/{corner}").unwrap();
+            }
+            obj.push('\n');
+        }
+    }
+    obj
+}
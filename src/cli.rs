@@ -0,0 +1,495 @@
+use scop::{
+    config::{parse_color, MAX_RENDER_SIZE},
+    graphics::Settings,
+    object_loader::loader,
+};
+use std::{error::Error, path::PathBuf};
+
+pub enum Command {
+    View(ViewArgs),
+    Info(PathBuf),
+    Convert { input: PathBuf, output: PathBuf },
+    Render { view: ViewArgs, output: PathBuf },
+    // the command whose help is printed by --help, or all of them
+    Help(Option<&'static str>),
+}
+
+// what is shown and how, by the view and render commands
+pub struct ViewArgs {
+    pub object: PathBuf,
+    pub texture: Option<PathBuf>,
+    pub overrides: Overrides,
+}
+
+// the settings given on the command line, which take precedence over the config
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    fov: Option<f32>,
+    bg_color: Option<(f32, f32, f32)>,
+    device: Option<String>,
+    render_size: Option<(u32, u32)>,
+    crease_angle: Option<f32>,
+}
+
+impl Overrides {
+    pub fn apply(self, settings: &mut Settings) {
+        if let Some(fov) = self.fov {
+            settings.fov = fov;
+        }
+        if let Some(bg_color) = self.bg_color {
+            settings.bg_color = bg_color;
+        }
+        if let Some(device) = self.device {
+            settings.device = Some(device);
+        }
+        if let Some(render_size) = self.render_size {
+            settings.render_size = render_size;
+        }
+        if let Some(crease_angle) = self.crease_angle {
+            settings.crease_angle = Some(crease_angle).filter(|&angle| angle > 0.0);
+        }
+    }
+}
+
+// the arguments are the ones following the program name. The first one is the command, it
+// may be left out to view an object as with `scop <object> [texture]`
+pub fn parse(args: &[String]) -> Result<Command, Box<dyn Error>> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err("missing object file".into()),
+    };
+    let (command, args) = match command {
+        "-h" | "--help" => return Ok(Command::Help(None)),
+        "help" => {
+            return match rest.first() {
+                Some(topic) => match COMMANDS.into_iter().find(|command| command == topic) {
+                    Some(command) => Ok(Command::Help(Some(command))),
+                    None => Err(format!("unknown command {topic}").into()),
+                },
+                None => Ok(Command::Help(None)),
+            };
+        }
+        "view" | "info" | "convert" | "render" => (command, rest),
+        _ => ("view", args),
+    };
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(Command::Help(
+            COMMANDS.into_iter().find(|name| *name == command),
+        ));
+    }
+
+    match command {
+        "info" => {
+            let args = Args::parse(args, &[])?;
+            let [object] = args.positional::<1, 1>(&["object file"])?;
+            Ok(Command::Info(object.unwrap().into()))
+        }
+        "convert" => {
+            let args = Args::parse(args, &[])?;
+            let [input, output] = args.positional::<2, 2>(&["input file", "output file"])?;
+            let output = PathBuf::from(output.unwrap());
+            if output
+                .extension()
+                .is_none_or(|extension| extension != "obj")
+            {
+                return Err("the output file must be an .obj file".into());
+            }
+            Ok(Command::Convert {
+                input: input.unwrap().into(),
+                output,
+            })
+        }
+        "render" => {
+            let args = Args::parse(args, &[VIEW_FLAGS, &["--output", "--size"]].concat())?;
+            let output = PathBuf::from(args.value("--output").ok_or("missing --output file")?);
+            if output
+                .extension()
+                .is_none_or(|extension| extension != "ppm")
+            {
+                return Err("the output file must be a .ppm image".into());
+            }
+            let mut view = parse_view(&args)?;
+            view.overrides.render_size = args.value("--size").map(parse_size).transpose()?;
+            Ok(Command::Render { view, output })
+        }
+        _ => {
+            let args = Args::parse(args, VIEW_FLAGS)?;
+            parse_view(&args).map(Command::View)
+        }
+    }
+}
+
+const COMMANDS: [&str; 4] = ["view", "info", "convert", "render"];
+const VIEW_FLAGS: &[&str] = &["--texture", "--fov", "--bg", "--device", "--crease"];
+
+fn parse_view(args: &Args) -> Result<ViewArgs, Box<dyn Error>> {
+    let [object, texture] = args.positional::<1, 2>(&["object file", "texture file"])?;
+    let texture = match (texture, args.value("--texture")) {
+        (Some(_), Some(_)) => return Err("the texture is given twice".into()),
+        (texture, flag) => texture.or(flag).map(PathBuf::from),
+    };
+
+    let overrides = Overrides {
+        fov: args.value("--fov").map(parse_fov).transpose()?,
+        bg_color: args.value("--bg").map(parse_color).transpose()?,
+        device: args.value("--device").map(String::from),
+        render_size: None,
+        crease_angle: args.value("--crease").map(parse_crease).transpose()?,
+    };
+
+    Ok(ViewArgs {
+        object: object.unwrap().into(),
+        texture,
+        overrides,
+    })
+}
+
+// the positional arguments and the values of the flags of a command. A flag is followed by
+// its value, either as the next argument or after a =
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    flags: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String], flags: &[&str]) -> Result<Self, Box<dyn Error>> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            flags: Vec::new(),
+        };
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            // what follows -- is never a flag
+            if arg == "--" {
+                parsed.positional.extend(args);
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                parsed.positional.push(arg);
+                continue;
+            }
+
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg, None),
+            };
+            let name = if name == "-o" { "--output" } else { name };
+            if !flags.contains(&name) {
+                return Err(format!("unknown option {name}").into());
+            }
+            let value = match value {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("missing value after {name}"))?,
+            };
+            if parsed.value(name).is_some() {
+                return Err(format!("{name} is given twice").into());
+            }
+            parsed.flags.push((name, value));
+        }
+        Ok(parsed)
+    }
+
+    fn value(&self, flag: &str) -> Option<&'a str> {
+        self.flags
+            .iter()
+            .find(|(name, _)| *name == flag)
+            .map(|(_, value)| *value)
+    }
+
+    // from MIN to MAX positional arguments, whose names are used by the errors
+    fn positional<const MIN: usize, const MAX: usize>(
+        &self,
+        names: &[&str; MAX],
+    ) -> Result<[Option<&'a str>; MAX], Box<dyn Error>> {
+        if self.positional.len() < MIN {
+            return Err(format!("missing {}", names[self.positional.len()]).into());
+        }
+        if let Some(arg) = self.positional.get(MAX) {
+            return Err(format!("unexpected argument {arg}").into());
+        }
+        Ok(std::array::from_fn(|i| self.positional.get(i).copied()))
+    }
+}
+
+// in degrees, returned in radians
+fn parse_fov(value: &str) -> Result<f32, Box<dyn Error>> {
+    match value.parse::<f32>() {
+        Ok(fov) if fov > 0.0 && fov < 180.0 => Ok(fov.to_radians()),
+        _ => Err(
+            format!("invalid field of view {value}, it must be between 0 and 180 degrees").into(),
+        ),
+    }
+}
+
+// in degrees, returned in radians
+fn parse_crease(value: &str) -> Result<f32, Box<dyn Error>> {
+    match value.parse::<f32>() {
+        Ok(angle) if (0.0..=180.0).contains(&angle) => Ok(angle.to_radians()),
+        _ => Err(format!("invalid crease angle {value}, it must be from 0 to 180 degrees").into()),
+    }
+}
+
+// WIDTHxHEIGHT, in pixels
+fn parse_size(value: &str) -> Result<(u32, u32), Box<dyn Error>> {
+    let size = value.split_once('x').and_then(|(width, height)| {
+        Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+    });
+    match size {
+        Some((width, height))
+            if (1..=MAX_RENDER_SIZE).contains(&width)
+                && (1..=MAX_RENDER_SIZE).contains(&height) =>
+        {
+            Ok((width, height))
+        }
+        _ => Err(format!(
+            "invalid size {value}, it must be WIDTHxHEIGHT with both from 1 to {MAX_RENDER_SIZE}"
+        )
+        .into()),
+    }
+}
+
+// the help of a command, or of all of them. The defaults it gives are the ones of `settings`
+pub fn help(command: Option<&str>, settings: &Settings) -> String {
+    let view_options = format!(
+        "  \
+        --texture <file>     the texture, a grid of numbered cells by default
+  --fov <degrees>      the vertical field of view, {} by default
+  --bg <color>         the background color, as r,g,b from 0 to 255 or as #rrggbb
+  --device <device>    the index or a part of the name of the vulkan device to use
+  --crease <degrees>   smooths the faces without normals with their neighbours less than
+                       this angle away, 0 keeps them flat, {} by default",
+        degrees(settings.fov),
+        settings.crease_angle.map_or(0.0, degrees)
+    );
+    let formats = format!(
+        "objects: {}\ntextures: {}",
+        loader::supported_meshes(),
+        loader::supported_images()
+    );
+
+    match command {
+        Some("view") => format!(
+            "usage: scop view <object> [texture] [options]\n\n\
+             shows the object in a window\n\noptions:\n{view_options}\n\n{formats}"
+        ),
+        Some("info") => "usage: scop info <object>\n\n\
+             prints the size, the groups and the materials of the object"
+            .to_string(),
+        Some("convert") => "usage: scop convert <input> <output.obj>\n\n\
+             writes the object as an obj file, with its materials in an mtl file of the same \
+             name"
+            .to_string(),
+        Some("render") => format!(
+            "usage: scop render <object> -o <output.ppm> [options]\n\n\
+             renders the object as it is first seen in the window to a ppm image, textured \
+             when a texture is given\n\noptions:\n\
+             \x20 -o, --output <file>  the ppm image to write\n\
+             \x20 --size <size>        WIDTHxHEIGHT in pixels, {}x{} by default\n{view_options}",
+            settings.render_size.0, settings.render_size.1
+        ),
+        _ => format!(
+            "usage: scop [view] <object> [texture] [options]\n\
+             \x20      scop <command> [arguments]\n\n\
+             commands:\n\
+             \x20 view     shows an object in a window, the default command\n\
+             \x20 info     prints what an object is made of\n\
+             \x20 convert  writes an object as an obj file\n\
+             \x20 render   renders an object to a ppm image\n\n\
+             run `scop help <command>` for the options of a command\n\n{formats}"
+        ),
+    }
+}
+
+// an angle for the help, rounded to a tenth of a degree
+fn degrees(radians: f32) -> f32 {
+    (radians.to_degrees() * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(&strings(args)) {
+            Ok(_) => panic!("{args:?} is parsed"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn positional() {
+        let args = strings(&["teapot.obj", "-", "--", "--fov"]);
+        let parsed = Args::parse(&args, VIEW_FLAGS).unwrap();
+        assert_eq!(parsed.positional, ["teapot.obj", "-", "--fov"]);
+        assert!(parsed.flags.is_empty());
+        assert_eq!(
+            parsed
+                .positional::<1, 3>(&["object", "texture", "other"])
+                .unwrap(),
+            [Some("teapot.obj"), Some("-"), Some("--fov")]
+        );
+
+        let names = ["object file", "texture file"];
+        let args = strings(&["teapot.obj"]);
+        let parsed = Args::parse(&args, &[]).unwrap();
+        assert_eq!(
+            parsed.positional::<1, 2>(&names).unwrap(),
+            [Some("teapot.obj"), None]
+        );
+        let missing = parsed.positional::<2, 2>(&names).unwrap_err();
+        assert_eq!(missing.to_string(), "missing texture file");
+        let args = strings(&["teapot.obj", "brick.png", "extra.png"]);
+        let parsed = Args::parse(&args, &[]).unwrap();
+        let extra = parsed.positional::<1, 2>(&names).unwrap_err();
+        assert_eq!(extra.to_string(), "unexpected argument extra.png");
+    }
+
+    #[test]
+    fn flags() {
+        let args = strings(&["--fov", "60", "teapot.obj", "--bg=#282828", "-o", "out.ppm"]);
+        let parsed = Args::parse(&args, &[VIEW_FLAGS, &["--output"]].concat()).unwrap();
+        assert_eq!(parsed.positional, ["teapot.obj"]);
+        assert_eq!(parsed.value("--fov"), Some("60"));
+        assert_eq!(parsed.value("--bg"), Some("#282828"));
+        assert_eq!(parsed.value("--output"), Some("out.ppm"));
+        assert_eq!(parsed.value("--device"), None);
+
+        for (args, expected) in [
+            (&["teapot.obj", "--zoom", "2"][..], "unknown option --zoom"),
+            (&["teapot.obj", "-o", "out.ppm"], "unknown option --output"),
+            (&["teapot.obj", "--fov"], "missing value after --fov"),
+            (
+                &["--fov=60", "teapot.obj", "--fov", "70"],
+                "--fov is given twice",
+            ),
+        ] {
+            let args = strings(args);
+            let error = Args::parse(&args, VIEW_FLAGS).err().unwrap();
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("640x480").unwrap(), (640, 480));
+        assert_eq!(
+            parse_size(&format!("1x{MAX_RENDER_SIZE}")).unwrap(),
+            (1, MAX_RENDER_SIZE)
+        );
+        for size in [
+            "640",
+            "640x",
+            "x480",
+            "640x480x2",
+            "0x480",
+            "-640x480",
+            "640X480",
+            &format!("{}x480", MAX_RENDER_SIZE + 1),
+        ] {
+            assert!(parse_size(size).is_err(), "{size}");
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let Ok(Command::View(view)) = parse(&strings(&["teapot.obj", "brick.png"])) else {
+            panic!("not a view command");
+        };
+        assert_eq!(view.object, PathBuf::from("teapot.obj"));
+        assert_eq!(view.texture, Some(PathBuf::from("brick.png")));
+        let Ok(Command::View(view)) = parse(&strings(&["view", "--texture=a.png", "info"])) else {
+            panic!("not a view command");
+        };
+        assert_eq!(view.object, PathBuf::from("info"));
+        assert_eq!(view.texture, Some(PathBuf::from("a.png")));
+
+        let Ok(Command::Info(path)) = parse(&strings(&["info", "teapot.obj"])) else {
+            panic!("not an info command");
+        };
+        assert_eq!(path, PathBuf::from("teapot.obj"));
+        let Ok(Command::Convert { input, output }) =
+            parse(&strings(&["convert", "in.stl", "out.obj"]))
+        else {
+            panic!("not a convert command");
+        };
+        assert_eq!((input, output), ("in.stl".into(), "out.obj".into()));
+
+        let args = [
+            "render",
+            "teapot.obj",
+            "--size",
+            "640x480",
+            "--output=out.ppm",
+        ];
+        let Ok(Command::Render { view, output }) = parse(&strings(&args)) else {
+            panic!("not a render command");
+        };
+        assert_eq!(output, PathBuf::from("out.ppm"));
+        let mut settings = Settings::default();
+        view.overrides.apply(&mut settings);
+        assert_eq!(settings.render_size, (640, 480));
+
+        for (args, topic) in [
+            (&["--help"][..], None),
+            (&["help"], None),
+            (&["help", "render"], Some("render")),
+            (&["info", "teapot.obj", "-h"], Some("info")),
+            (&["teapot.obj", "--help"], Some("view")),
+        ] {
+            let Ok(Command::Help(command)) = parse(&strings(args)) else {
+                panic!("{args:?} is not a help command");
+            };
+            assert_eq!(command, topic);
+        }
+
+        assert_eq!(error(&[]), "missing object file");
+        assert_eq!(error(&["help", "show"]), "unknown command show");
+        assert_eq!(error(&["info"]), "missing object file");
+        assert_eq!(
+            error(&["info", "a.obj", "b.obj"]),
+            "unexpected argument b.obj"
+        );
+        assert_eq!(
+            error(&["info", "a.obj", "--fov", "60"]),
+            "unknown option --fov"
+        );
+        assert_eq!(error(&["convert", "a.stl"]), "missing output file");
+        assert!(error(&["convert", "a.stl", "b.ply"]).contains(".obj"));
+        assert_eq!(
+            error(&["render", "a.obj", "--size", "640x480"]),
+            "missing --output file"
+        );
+        assert!(error(&["render", "a.obj", "-o", "a.png"]).contains(".ppm"));
+        assert!(error(&["render", "a.obj", "-o", "a.ppm", "--size", "640"]).contains("640"));
+        assert!(error(&["a.obj", "--size", "640x480"]).contains("--size"));
+        assert_eq!(
+            error(&["a.obj", "b.png", "--texture", "c.png"]),
+            "the texture is given twice"
+        );
+        assert!(error(&["a.obj", "--fov", "180"]).contains("field of view"));
+        assert!(error(&["a.obj", "--crease", "-1"]).contains("crease angle"));
+    }
+
+    #[test]
+    fn help_defaults() {
+        let mut settings = Settings::default();
+        let text = help(Some("render"), &settings);
+        assert!(text.contains("the vertical field of view, 45.8 by default"));
+        assert!(text.contains("1280x720 by default"));
+        assert!(text.contains("0 keeps them flat, 0 by default"));
+
+        settings.fov = 60f32.to_radians();
+        settings.render_size = (640, 480);
+        settings.crease_angle = Some(30f32.to_radians());
+        let text = help(Some("render"), &settings);
+        assert!(text.contains("the vertical field of view, 60 by default"));
+        assert!(text.contains("640x480 by default"));
+        assert!(text.contains("0 keeps them flat, 30 by default"));
+    }
+}
@@ -0,0 +1,321 @@
+pub mod toml;
+
+use crate::{
+    graphics::{
+        input::{parse_binding, Action},
+        Settings,
+    },
+    math::Vec3,
+};
+use std::{
+    env,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use toml::Toml;
+
+// the largest width or height of a rendered image
+pub const MAX_RENDER_SIZE: u32 = 16384;
+
+// the default settings, changed by the config of the user in $XDG_CONFIG_HOME/scop/config.toml
+// (or ~/.config/scop/config.toml), then by the one of the project, scop.toml in the working
+// directory. Both are optional
+pub fn load() -> Result<Settings, Box<dyn Error>> {
+    load_files(
+        user_config()
+            .into_iter()
+            .chain([PathBuf::from("scop.toml")]),
+    )
+}
+
+// the default settings changed by each of the files in turn, the missing ones are skipped
+fn load_files(paths: impl IntoIterator<Item = PathBuf>) -> Result<Settings, Box<dyn Error>> {
+    let mut settings = Settings::default();
+    for path in paths {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("failed to read {}: {e}", path.display()).into()),
+        };
+        Toml::parse(&text)
+            .and_then(|config| apply(&config, &mut settings, &path))
+            .map_err(|e| format!("invalid config {}: {e}", path.display()))?;
+    }
+    Ok(settings)
+}
+
+fn user_config() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("scop").join("config.toml"))
+}
+
+// the settings missing from the config are kept, the unknown ones are skipped with a warning
+fn apply(config: &Toml, settings: &mut Settings, path: &Path) -> Result<(), Box<dyn Error>> {
+    // the tables of the root, which is one
+    for (section, table) in config.as_table().unwrap() {
+        let Some(entries) = table.as_table() else {
+            return Err(format!("{section} is {} instead of a table", table.type_name()).into());
+        };
+        for (key, value) in entries {
+            let value = Setting {
+                name: format!("{section}.{key}"),
+                value,
+            };
+            match (section.as_str(), key.as_str()) {
+                ("colors", "background") => settings.bg_color = value.color()?,
+                ("camera", "fov") => {
+                    settings.fov = value.number(|fov| fov > 0.0 && fov < 180.0)?.to_radians()
+                }
+                ("camera", "near") => settings.near = value.number(|near| near > 0.0)?,
+                ("camera", "far") => settings.far = value.number(|far| far > 0.0)?,
+                ("camera", "distance") => {
+                    settings.camera_distance = value.number(|distance| distance > 0.0)?
+                }
+                ("light", "colors") => {
+                    let colors = value.array()?;
+                    if colors.is_empty() {
+                        return Err(format!("{} has no colors", value.name).into());
+                    }
+                    settings.light_colors = colors
+                        .iter()
+                        .map(|color| {
+                            let (r, g, b) = color.color()?;
+                            Ok(Vec3 {
+                                x: r / 255.0,
+                                y: g / 255.0,
+                                z: b / 255.0,
+                            })
+                        })
+                        .collect::<Result<_, Box<dyn Error>>>()?;
+                }
+                ("light", "intensity") => {
+                    settings.light_intensity = value.number(|intensity| intensity >= 0.0)?
+                }
+                ("light", "ambient") => {
+                    settings.ambient_intensity = value.number(|intensity| intensity >= 0.0)?
+                }
+                // in degrees, 0 keeps the faces flat
+                ("mesh", "crease_angle") => {
+                    let angle = value.number(|angle| (0.0..=180.0).contains(&angle))?;
+                    settings.crease_angle = Some(angle.to_radians()).filter(|&angle| angle > 0.0)
+                }
+                ("render", "point_size") => {
+                    settings.point_size = value.number(|size| size > 0.0)?
+                }
+                ("render", "width") => settings.render_size.0 = value.render_size()?,
+                ("render", "height") => settings.render_size.1 = value.render_size()?,
+                ("render", "device") => settings.device = Some(value.string()?.to_string()),
+                ("controls", action) => match Action::from_name(action) {
+                    Some(action) => {
+                        // the bindings of the config replace the default ones of the action
+                        let bindings = match value.value {
+                            Toml::Array(_) => value.array()?,
+                            _ => vec![value],
+                        };
+                        settings.controls.retain(|(_, bound)| *bound != action);
+                        for binding in bindings {
+                            let parsed = parse_binding(binding.string()?)
+                                .map_err(|e| format!("{}: {e}", binding.name))?;
+                            settings.controls.push((parsed, action));
+                        }
+                    }
+                    None => println!(
+                        "config warning: {}: unknown action {action}",
+                        path.display()
+                    ),
+                },
+                _ => println!(
+                    "config warning: {}: unknown setting {}",
+                    path.display(),
+                    value.name
+                ),
+            }
+        }
+    }
+    if settings.near >= settings.far {
+        return Err("the near clipping plane is not closer than the far one".into());
+    }
+    Ok(())
+}
+
+// a value of the config and its name, for the errors
+struct Setting<'a> {
+    name: String,
+    value: &'a Toml,
+}
+
+impl<'a> Setting<'a> {
+    fn invalid(&self, expected: &str) -> Box<dyn Error> {
+        format!(
+            "{} is {} instead of {expected}",
+            self.name,
+            self.value.type_name()
+        )
+        .into()
+    }
+
+    // a number for which `valid` is true
+    fn number(&self, valid: impl Fn(f32) -> bool) -> Result<f32, Box<dyn Error>> {
+        let number = self
+            .value
+            .as_f64()
+            .ok_or_else(|| self.invalid("a number"))? as f32;
+        if !valid(number) {
+            return Err(format!("invalid {} {number}", self.name).into());
+        }
+        Ok(number)
+    }
+
+    fn render_size(&self) -> Result<u32, Box<dyn Error>> {
+        let size = self
+            .value
+            .as_i64()
+            .ok_or_else(|| self.invalid("an integer"))?;
+        match u32::try_from(size) {
+            Ok(size) if (1..=MAX_RENDER_SIZE).contains(&size) => Ok(size),
+            _ => Err(format!("{} {size} is not from 1 to {MAX_RENDER_SIZE}", self.name).into()),
+        }
+    }
+
+    fn string(&self) -> Result<&'a str, Box<dyn Error>> {
+        self.value.as_str().ok_or_else(|| self.invalid("a string"))
+    }
+
+    // the elements of an array, named after their index
+    fn array(&self) -> Result<Vec<Setting<'a>>, Box<dyn Error>> {
+        let values = self
+            .value
+            .as_array()
+            .ok_or_else(|| self.invalid("an array"))?;
+        Ok(values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Setting {
+                name: format!("{}[{i}]", self.name),
+                value,
+            })
+            .collect())
+    }
+
+    // either a string of the command line format or an array of 3 values from 0 to 255
+    fn color(&self) -> Result<(f32, f32, f32), Box<dyn Error>> {
+        if let Some(color) = self.value.as_str() {
+            return parse_color(color).map_err(|e| format!("{}: {e}", self.name).into());
+        }
+        let channels = self
+            .value
+            .as_array()
+            .filter(|channels| channels.len() == 3)
+            .ok_or_else(|| self.invalid("a color"))?;
+        let mut color = [0.0; 3];
+        for (channel, value) in color.iter_mut().zip(channels) {
+            *channel = value
+                .as_i64()
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(|| format!("{} has channels out of 0 to 255", self.name))?
+                as f32;
+        }
+        Ok(color.into())
+    }
+}
+
+// either r,g,b from 0 to 255 or #rrggbb
+pub fn parse_color(value: &str) -> Result<(f32, f32, f32), Box<dyn Error>> {
+    let invalid = || format!("invalid color {value}, it must be r,g,b or #rrggbb");
+    let channels: Vec<u8> = match value.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.is_ascii() => (0..3)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?,
+        Some(_) => return Err(invalid().into()),
+        None => value
+            .split(',')
+            .map(|channel| channel.trim().parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?,
+    };
+    match *channels {
+        [r, g, b] => Ok((r as f32, g as f32, b as f32)),
+        _ => Err(invalid().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::input::Binding;
+
+    #[test]
+    fn project_overrides_user() {
+        let dir = env::temp_dir().join(format!("scop-test-{}-config", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (user, project) = (dir.join("config.toml"), dir.join("scop.toml"));
+        fs::write(
+            &user,
+            "[camera]\nfov = 60\nfar = 50\n[light]\nintensity = 2\n[controls]\nquit = \"q\"\n",
+        )
+        .unwrap();
+        fs::write(
+            &project,
+            "[camera]\nfov = 90\n[controls]\nquit = [\"x\", \"physical:y\"]\n",
+        )
+        .unwrap();
+
+        let paths = [user.clone(), dir.join("missing.toml"), project.clone()];
+        let settings = load_files(paths);
+        // a setting of the project that only conflicts with the one of the user
+        fs::write(&project, "[camera]\nnear = 100\n").unwrap();
+        let conflict = load_files([user, project]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let settings = settings.unwrap();
+        let defaults = Settings::default();
+        assert_eq!(settings.fov, 90f32.to_radians());
+        assert_eq!(settings.far, 50.0);
+        assert_eq!(settings.near, defaults.near);
+        assert_eq!(settings.light_intensity, 2.0);
+        assert_eq!(settings.ambient_intensity, defaults.ambient_intensity);
+        let quit: Vec<&Binding> = settings
+            .controls
+            .iter()
+            .filter(|(_, action)| *action == Action::Quit)
+            .map(|(binding, _)| binding)
+            .collect();
+        assert_eq!(
+            quit,
+            [
+                &parse_binding("x").unwrap(),
+                &parse_binding("physical:y").unwrap()
+            ]
+        );
+        assert_eq!(settings.controls.len(), defaults.controls.len() + 1);
+        assert!(conflict.is_err());
+    }
+
+    #[test]
+    fn crease_angle() {
+        let apply = |text: &str, settings: &mut Settings| {
+            apply(&Toml::parse(text)?, settings, Path::new("scop.toml"))
+        };
+        let mut settings = Settings::default();
+        assert_eq!(settings.crease_angle, None);
+        apply("[mesh]\ncrease_angle = 60\n", &mut settings).unwrap();
+        assert_eq!(settings.crease_angle, Some(60f32.to_radians()));
+        // a later config turns the smoothing off
+        apply("[mesh]\ncrease_angle = 0\n", &mut settings).unwrap();
+        assert_eq!(settings.crease_angle, None);
+
+        for text in [
+            "crease_angle = -1",
+            "crease_angle = 181",
+            "crease_angle = \"60\"",
+        ] {
+            let text = format!("[mesh]\n{text}\n");
+            assert!(apply(&text, &mut settings).is_err(), "{text}");
+        }
+    }
+}
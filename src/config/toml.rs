@@ -0,0 +1,631 @@
+use std::error::Error;
+
+// a parsed toml value, the keys of tables keep the order of the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Toml {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Toml>),
+    Table(Vec<(String, Toml)>),
+}
+
+impl Toml {
+    // parses the part of toml a config needs: tables, dotted keys, strings, numbers, booleans,
+    // arrays and inline tables. Multi-line strings, dates and arrays of tables are rejected
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let mut root = Vec::new();
+        // the keys of the tables defined by a header, which can't be defined twice
+        let mut defined: Vec<Vec<String>> = Vec::new();
+        let mut table: Vec<String> = Vec::new();
+        loop {
+            parser.skip_whitespace(true);
+            match parser.text.get(parser.pos) {
+                None => break,
+                Some(b'[') => {
+                    parser.pos += 1;
+                    if parser.text.get(parser.pos) == Some(&b'[') {
+                        return Err(parser.error("arrays of tables are not supported"));
+                    }
+                    table = parser.key()?;
+                    parser.expect(b']')?;
+                    if defined.contains(&table) {
+                        return Err(
+                            parser.error(&format!("table {} is defined twice", table.join(".")))
+                        );
+                    }
+                    defined.push(table.clone());
+                    insert(&mut root, &table, None).map_err(|e| parser.error(&e))?;
+                }
+                Some(_) => {
+                    let key = parser.key()?;
+                    parser.expect(b'=')?;
+                    let value = parser.value()?;
+                    let path = [&table[..], &key[..]].concat();
+                    insert(&mut root, &path, Some(value)).map_err(|e| parser.error(&e))?;
+                }
+            }
+            parser.end_of_line()?;
+        }
+        Ok(Toml::Table(root))
+    }
+
+    // integers are numbers as well
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(n) => Some(*n as f64),
+            Self::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Toml]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&[(String, Toml)]> {
+        match self {
+            Self::Table(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    // for the errors about values of the wrong type
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "a boolean",
+            Self::Integer(_) => "an integer",
+            Self::Float(_) => "a float",
+            Self::String(_) => "a string",
+            Self::Array(_) => "an array",
+            Self::Table(_) => "a table",
+        }
+    }
+}
+
+// inserts `value` at the dotted `path`, creating the tables on the way. Without a value, the
+// table at `path` is only created
+fn insert(
+    mut table: &mut Vec<(String, Toml)>,
+    path: &[String],
+    value: Option<Toml>,
+) -> Result<(), String> {
+    let Some((last, parents)) = path.split_last() else {
+        return Ok(());
+    };
+    for (i, name) in parents
+        .iter()
+        .chain(value.is_none().then_some(last))
+        .enumerate()
+    {
+        let index = match table.iter().position(|(key, _)| key == name) {
+            Some(index) => index,
+            None => {
+                table.push((name.clone(), Toml::Table(Vec::new())));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            Toml::Table(entries) => entries,
+            _ => return Err(format!("{} is not a table", path[..=i].join("."))),
+        };
+    }
+    if let Some(value) = value {
+        if table.iter().any(|(key, _)| key == last) {
+            return Err(format!("{} is defined twice", path.join(".")));
+        }
+        table.push((last.clone(), value));
+    }
+    Ok(())
+}
+
+// deeper values are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> Box<dyn Error> {
+        let line = self.text[..self.pos]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1;
+        format!("toml line {line}: {reason}").into()
+    }
+
+    // skips spaces and comments, and the ends of lines when `newlines` is set
+    fn skip_whitespace(&mut self, newlines: bool) {
+        while let Some(&byte) = self.text.get(self.pos) {
+            match byte {
+                b' ' | b'\t' => self.pos += 1,
+                b'\n' | b'\r' if newlines => self.pos += 1,
+                b'#' => {
+                    while self.text.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self, newlines: bool) -> Option<u8> {
+        self.skip_whitespace(newlines);
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Box<dyn Error>> {
+        if self.peek(false) != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // nothing but a comment may follow a key and its value on their line
+    fn end_of_line(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.peek(false) {
+            None | Some(b'\n') => Ok(()),
+            Some(b'\r') if self.text.get(self.pos + 1) == Some(&b'\n') => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    // a key made of bare or quoted parts separated by dots
+    fn key(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut parts = Vec::new();
+        loop {
+            let part = match self.peek(false) {
+                Some(b'"') => self.string()?,
+                Some(b'\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .text
+                        .get(self.pos)
+                        .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_' || *b == b'-')
+                    {
+                        self.pos += 1;
+                    }
+                    if self.pos == start {
+                        return Err(self.error("expected a key"));
+                    }
+                    // bare keys are ascii
+                    String::from_utf8(self.text[start..self.pos].to_vec()).unwrap()
+                }
+            };
+            parts.push(part);
+            if self.peek(false) != Some(b'.') {
+                return Ok(parts);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Toml, Box<dyn Error>> {
+        match self.peek(false) {
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::inline_table),
+            Some(b'"') => Ok(Toml::String(self.string()?)),
+            Some(b'\'') => Ok(Toml::String(self.literal_string()?)),
+            Some(b't') => self.literal("true", Toml::Bool(true)),
+            Some(b'f') => self.literal("false", Toml::Bool(false)),
+            Some(b'+' | b'-' | b'0'..=b'9' | b'i' | b'n') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Toml, Box<dyn Error>>,
+    ) -> Result<Toml, Box<dyn Error>> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    // arrays may span several lines and end with a comma
+    fn array(&mut self) -> Result<Toml, Box<dyn Error>> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        loop {
+            if self.peek(true) == Some(b']') {
+                self.pos += 1;
+                return Ok(Toml::Array(values));
+            }
+            values.push(self.value()?);
+            match self.peek(true) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Toml::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    // inline tables stay on one line
+    fn inline_table(&mut self) -> Result<Toml, Box<dyn Error>> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        if self.peek(false) == Some(b'}') {
+            self.pos += 1;
+            return Ok(Toml::Table(entries));
+        }
+        loop {
+            let key = self.key()?;
+            self.expect(b'=')?;
+            let value = self.value()?;
+            insert(&mut entries, &key, Some(value)).map_err(|e| self.error(&e))?;
+            match self.peek(false) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Toml::Table(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Toml) -> Result<Toml, Box<dyn Error>> {
+        if !self.text[self.pos..].starts_with(word.as_bytes()) {
+            return Err(self.error("unexpected character"));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    // integers, which may be written in hexadecimal, octal or binary, and floats. Digits
+    // may be separated by underscores
+    fn number(&mut self) -> Result<Toml, Box<dyn Error>> {
+        let start = self.pos;
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b"+-._".contains(b))
+        {
+            self.pos += 1;
+        }
+        // the text is valid utf-8 and the number is ascii
+        let number = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+        let invalid = || self.error(&format!("invalid number {number}"));
+        if number.starts_with('_') || number.ends_with('_') || number.contains("__") {
+            return Err(invalid());
+        }
+        let digits = number.replace('_', "");
+
+        let (sign, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        let radix = match unsigned.get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            return i64::from_str_radix(&unsigned[2..], radix)
+                .ok()
+                .filter(|_| sign == 1)
+                .map(Toml::Integer)
+                .ok_or_else(invalid);
+        }
+        match unsigned {
+            "inf" => return Ok(Toml::Float(sign as f64 * f64::INFINITY)),
+            "nan" => return Ok(Toml::Float(f64::NAN)),
+            _ => {}
+        }
+        if !unsigned.starts_with(|c: char| c.is_ascii_digit())
+            || (unsigned.len() > 1
+                && unsigned.starts_with('0')
+                && unsigned.as_bytes()[1].is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        if unsigned.contains(['.', 'e', 'E']) {
+            digits.parse().map(Toml::Float).map_err(|_| invalid())
+        } else {
+            digits.parse().map(Toml::Integer).map_err(|_| invalid())
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        self.expect(b'"')?;
+        if self.text[self.pos..].starts_with(b"\"\"") {
+            return Err(self.error("multi-line strings are not supported"));
+        }
+        let mut string = Vec::new();
+        loop {
+            let Some(&byte) = self.text.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self.text.get(self.pos).copied();
+                    self.pos += 1;
+                    let c = match escaped {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape(4)?,
+                        Some(b'U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    string.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b'\n' => return Err(self.error("unterminated string")),
+                0..=0x08 | 0x0a..=0x1f | 0x7f => {
+                    return Err(self.error("control character in a string"))
+                }
+                _ => string.push(byte),
+            }
+        }
+        // only whole utf-8 sequences were copied from the text
+        Ok(String::from_utf8(string).unwrap())
+    }
+
+    // the characters between single quotes, without escapes
+    fn literal_string(&mut self) -> Result<String, Box<dyn Error>> {
+        self.expect(b'\'')?;
+        if self.text[self.pos..].starts_with(b"''") {
+            return Err(self.error("multi-line strings are not supported"));
+        }
+        let start = self.pos;
+        loop {
+            match self.text.get(self.pos) {
+                Some(b'\'') => break,
+                None | Some(b'\n') => return Err(self.error("unterminated string")),
+                Some(_) => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        // the quotes are ascii, so the string is cut between whole utf-8 sequences
+        Ok(String::from_utf8(self.text[start..self.pos - 1].to_vec()).unwrap())
+    }
+
+    // the character of a \u or \U escape of `len` hexadecimal digits
+    fn unicode_escape(&mut self, len: usize) -> Result<char, Box<dyn Error>> {
+        let code = self
+            .text
+            .get(self.pos..self.pos + len)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += len;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(&str, Toml)]) -> Toml {
+        Toml::Table(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    fn string(s: &str) -> Toml {
+        Toml::String(s.to_string())
+    }
+
+    #[test]
+    fn tables_and_dotted_keys() {
+        let text = "title = \"scop\"
+[camera]
+fov = 60
+clip.near = 0.1
+\"quoted key\".x = 1
+[light.sun]
+intensity = 2.5
+[light]
+on = true
+";
+        let expected = table(&[
+            ("title", string("scop")),
+            (
+                "camera",
+                table(&[
+                    ("fov", Toml::Integer(60)),
+                    ("clip", table(&[("near", Toml::Float(0.1))])),
+                    ("quoted key", table(&[("x", Toml::Integer(1))])),
+                ]),
+            ),
+            (
+                "light",
+                table(&[
+                    ("sun", table(&[("intensity", Toml::Float(2.5))])),
+                    ("on", Toml::Bool(true)),
+                ]),
+            ),
+        ]);
+        assert_eq!(Toml::parse(text).unwrap(), expected);
+    }
+
+    #[test]
+    fn strings() {
+        let text = r#"basic = "a\"b\\c\td\n\u00e9\U0001F600"
+literal = 'C:\path\n "quoted"'
+raw = "é # not a comment"
+'literal key' = ""
+"#;
+        let expected = table(&[
+            ("basic", string("a\"b\\c\td\né\u{1f600}")),
+            ("literal", string("C:\\path\\n \"quoted\"")),
+            ("raw", string("é # not a comment")),
+            ("literal key", string("")),
+        ]);
+        assert_eq!(Toml::parse(text).unwrap(), expected);
+    }
+
+    #[test]
+    fn numbers() {
+        let text = "a = 0x1f\nb = 0o17\nc = 0b101\nd = 1_000\ne = -3\nf = +4\ng = 1e3\n\
+                    h = -0.5\ni = -inf\nj = 0\n";
+        let expected = table(&[
+            ("a", Toml::Integer(31)),
+            ("b", Toml::Integer(15)),
+            ("c", Toml::Integer(5)),
+            ("d", Toml::Integer(1000)),
+            ("e", Toml::Integer(-3)),
+            ("f", Toml::Integer(4)),
+            ("g", Toml::Float(1000.0)),
+            ("h", Toml::Float(-0.5)),
+            ("i", Toml::Float(f64::NEG_INFINITY)),
+            ("j", Toml::Integer(0)),
+        ]);
+        assert_eq!(Toml::parse(text).unwrap(), expected);
+        let nan = Toml::parse("nan = nan").unwrap();
+        assert!(nan.as_table().unwrap()[0].1.as_f64().unwrap().is_nan());
+    }
+
+    #[test]
+    fn arrays_and_inline_tables() {
+        let text = "colors = [
+  [255, 0, 0], # red
+  \"#00ff00\",
+
+]
+point = { x = 1, y.z = 'two' }
+empty = {}
+none = []
+";
+        let expected = table(&[
+            (
+                "colors",
+                Toml::Array(vec![
+                    Toml::Array(vec![Toml::Integer(255), Toml::Integer(0), Toml::Integer(0)]),
+                    string("#00ff00"),
+                ]),
+            ),
+            (
+                "point",
+                table(&[
+                    ("x", Toml::Integer(1)),
+                    ("y", table(&[("z", string("two"))])),
+                ]),
+            ),
+            ("empty", table(&[])),
+            ("none", Toml::Array(Vec::new())),
+        ]);
+        assert_eq!(Toml::parse(text).unwrap(), expected);
+    }
+
+    #[test]
+    fn comments() {
+        let text = "# the settings\r\n\r\n  [render] # of the images\r\nwidth = 640 # pixels\r\n\
+                    # height = 480\r\n";
+        let expected = table(&[("render", table(&[("width", Toml::Integer(640))]))]);
+        assert_eq!(Toml::parse(text).unwrap(), expected);
+        assert_eq!(Toml::parse("# nothing").unwrap(), table(&[]));
+    }
+
+    #[test]
+    fn invalid() {
+        let invalid = [
+            // defined twice
+            "a = 1\na = 2",
+            "[a]\n[a]",
+            "a.b = 1\n[a]\nb = 2",
+            "a = 1\na.b = 2",
+            "a = 1\n[a]",
+            "a = { x = 1, x = 2 }",
+            // unterminated strings
+            "s = \"abc",
+            "s = \"abc\nx\"",
+            "s = 'abc",
+            "s = 'abc\n'",
+            // bad values
+            "x =",
+            "x = tru",
+            "x = yes",
+            "x = 1__0",
+            "x = _1",
+            "x = 01",
+            "x = -0x1",
+            "x = 0xg",
+            "x = 1.2.3",
+            "x = 99999999999999999999",
+            "x = [1, 2",
+            "x = [1 2]",
+            "x = { a = 1",
+            "x = {\na = 1 }",
+            "x = 1 2",
+            "x = \"\\q\"",
+            "x = \"\\u12\"",
+            "x = \"\\uD800\"",
+            "x = \"tab\u{1}\"",
+            // what the parser does not support
+            "[[array]]",
+            "x = \"\"\"multi\"\"\"",
+            "x = '''multi'''",
+            // keys
+            "= 1",
+            "a. = 1",
+            "[a",
+        ];
+        for text in invalid {
+            assert!(Toml::parse(text).is_err(), "{text:?}");
+        }
+
+        let deep = format!(
+            "x = {}{}",
+            "[".repeat(MAX_DEPTH + 1),
+            "]".repeat(MAX_DEPTH + 1)
+        );
+        assert!(Toml::parse(&deep).is_err());
+        let shallow = format!("x = {}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Toml::parse(&shallow).is_ok());
+
+        let error = Toml::parse("a = 1\n\nb = 1 2\n").unwrap_err();
+        assert!(error.to_string().starts_with("toml line 3:"), "{error}");
+    }
+}
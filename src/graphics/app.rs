@@ -1,25 +1,28 @@
-use super::{input::InputState, App, Camera, Light, RenderContext, TimeInfo};
+use super::{
+    input::{Action, InputState},
+    App, Camera, Light, RenderContext, Settings, TimeInfo,
+};
 use crate::{
     math::Mat4,
-    object_loader::{texture::Texture, Object, Vertexxx},
-    BG_COLOR,
+    object_loader::{material::Material, texture::Texture, Object, Topology, Vertexxx},
 };
 use std::{error::Error, sync::Arc};
 use vulkano::{
     buffer::{
         allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
-        Buffer, BufferCreateInfo, BufferUsage,
+        Buffer, BufferCreateInfo, BufferUsage, Subbuffer,
     },
     command_buffer::{
         allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
-        CopyBufferToImageInfo, PrimaryCommandBufferAbstract, RenderPassBeginInfo,
+        CopyBufferToImageInfo, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract,
+        RenderPassBeginInfo,
     },
     descriptor_set::{
         allocator::StandardDescriptorSetAllocator, DescriptorSet, WriteDescriptorSet,
     },
     device::{
-        physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned,
-        QueueCreateInfo, QueueFlags,
+        physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, DeviceFeatures,
+        DeviceOwned, QueueCreateInfo, QueueFlags,
     },
     format::Format,
     image::{
@@ -27,13 +30,13 @@ use vulkano::{
         view::ImageView,
         Image, ImageCreateInfo, ImageType, ImageUsage,
     },
-    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
+    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     pipeline::{
         graphics::{
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
+            color_blend::{AttachmentBlend, ColorBlendAttachmentState, ColorBlendState},
             depth_stencil::{DepthState, DepthStencilState},
-            input_assembly::InputAssemblyState,
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
             multisample::MultisampleState,
             rasterization::RasterizationState,
             vertex_input::{Vertex, VertexDefinition},
@@ -63,16 +66,21 @@ use winit::{
 
 impl App {
     // this function creates the App object and initializes everything before creating a window
+    // without an event loop, the app has no window and only renders offscreen
     pub fn new(
-        event_loop: &EventLoop<()>,
+        event_loop: Option<&EventLoop<()>>,
         object: Object,
         texture: Texture,
+        settings: Settings,
     ) -> Result<Self, Box<dyn Error>> {
         // load the vulkan library and create an instance of it
         let library = VulkanLibrary::new()?;
 
         let instance = {
-            let required_extensions = Surface::required_extensions(event_loop)?;
+            let required_extensions = match event_loop {
+                Some(event_loop) => Surface::required_extensions(event_loop)?,
+                None => InstanceExtensions::empty(),
+            };
 
             Instance::new(
                 library,
@@ -85,14 +93,27 @@ impl App {
         };
 
         // selecting a physical device (eg. graphic card) and creating a Device and a queue from it that we will use to do all future operations
-        let (device, queue) = {
+        let (device, queue, point_size) = {
             let device_extensions = DeviceExtensions {
-                khr_swapchain: true,
+                khr_swapchain: event_loop.is_some(),
                 ..DeviceExtensions::empty()
             };
 
-            let (physical_device, queue_family_index) = instance
-                .enumerate_physical_devices()?
+            let physical_devices: Vec<_> = instance.enumerate_physical_devices()?.collect();
+            // the device asked for is either its index or a part of its name
+            let requested = |i: usize, name: &str| match &settings.device {
+                None => true,
+                Some(device) => match device.parse::<usize>() {
+                    Ok(index) => index == i,
+                    Err(_) => name.to_lowercase().contains(&device.to_lowercase()),
+                },
+            };
+
+            let (physical_device, queue_family_index) = physical_devices
+                .iter()
+                .enumerate()
+                .filter(|(i, p)| requested(*i, &p.properties().device_name))
+                .map(|(_, p)| p.clone())
                 .filter(|p| p.supported_extensions().contains(&device_extensions))
                 .filter_map(|p| {
                     p.queue_family_properties()
@@ -100,7 +121,9 @@ impl App {
                         .enumerate()
                         .position(|(i, q)| {
                             q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                                && p.presentation_support(i as u32, event_loop).unwrap()
+                                && event_loop.is_none_or(|event_loop| {
+                                    p.presentation_support(i as u32, event_loop).unwrap()
+                                })
                         })
                         .map(|i| (p, i as u32))
                 })
@@ -112,7 +135,21 @@ impl App {
                     PhysicalDeviceType::Other => 4,
                     _ => 5,
                 })
-                .unwrap();
+                .ok_or_else(|| {
+                    let devices: Vec<String> = physical_devices
+                        .iter()
+                        .enumerate()
+                        .map(|(i, p)| format!("{i}: {}", p.properties().device_name))
+                        .collect();
+                    let requested = match &settings.device {
+                        Some(device) => format!(" matching {device}"),
+                        None => String::new(),
+                    };
+                    format!(
+                        "no vulkan device{requested} can render, the devices are: {}",
+                        devices.join(", ")
+                    )
+                })?;
 
             println!(
                 "Using physical device: {} (type: {:?})",
@@ -120,10 +157,23 @@ impl App {
                 physical_device.properties().device_type
             );
 
+            // points bigger than a pixel need the large_points feature
+            let large_points = physical_device.supported_features().large_points;
+            let point_size = if large_points {
+                let [min, max] = physical_device.properties().point_size_range;
+                settings.point_size.clamp(min, max)
+            } else {
+                1.0
+            };
+
             let (device, mut queues) = Device::new(
                 physical_device,
                 DeviceCreateInfo {
                     enabled_extensions: device_extensions,
+                    enabled_features: DeviceFeatures {
+                        large_points,
+                        ..DeviceFeatures::empty()
+                    },
                     queue_create_infos: vec![QueueCreateInfo {
                         queue_family_index,
                         ..Default::default()
@@ -132,7 +182,7 @@ impl App {
                 },
             )?;
 
-            (device, queues.next().unwrap())
+            (device, queues.next().unwrap(), point_size)
         };
 
         // creating allocators for vulkan
@@ -173,77 +223,71 @@ impl App {
             object.vertex.clone(),
         )?;
 
-        let index_buffer = Buffer::from_iter(
-            memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::INDEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            object.indice.clone(),
-        )?;
-
-        // creating the texture
-        let mut uploads = AutoCommandBufferBuilder::primary(
-            command_buffer_allocator.clone(),
-            queue.queue_family_index(),
-            CommandBufferUsage::OneTimeSubmit,
-        )
-        .unwrap();
-
-        let texture = {
-            let format = Format::R8G8B8A8_UNORM;
-            let extent: [u32; 3] = [texture.width, texture.height, 1];
-
-            let upload_buffer = Buffer::new_slice(
+        // point clouds have no indices
+        let index_buffer = match object.topology {
+            Topology::Triangles => Some(Buffer::from_iter(
                 memory_allocator.clone(),
                 BufferCreateInfo {
-                    usage: BufferUsage::TRANSFER_SRC,
+                    usage: BufferUsage::INDEX_BUFFER,
                     ..Default::default()
                 },
                 AllocationCreateInfo {
-                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
                         | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                     ..Default::default()
                 },
-                (texture.width * texture.height * 4) as DeviceSize,
-            )
-            .unwrap();
-
-            {
-                let buf = &mut upload_buffer.write().unwrap();
-
-                for i in 0..(texture.width * texture.height * 4) as usize {
-                    buf[i] = texture.data[i]
-                }
-            }
-
-            let image = Image::new(
-                memory_allocator.clone(),
-                ImageCreateInfo {
-                    image_type: ImageType::Dim2d,
-                    format,
-                    extent,
-                    usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-            )
-            .unwrap();
+                object.indice.clone(),
+            )?),
+            Topology::Points => None,
+        };
 
-            uploads
-                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
-                    upload_buffer,
-                    image.clone(),
-                ))
-                .unwrap();
+        // creating the texture
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            command_buffer_allocator.clone(),
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
 
-            ImageView::new_default(image).unwrap()
-        };
+        let texture = upload_texture(&memory_allocator, &mut uploads, &texture);
+
+        // every submesh gets its material data, and its own texture when the material has one
+        let default_material = Material::default();
+        let materials = object
+            .submeshes
+            .iter()
+            .map(|submesh| {
+                let material = submesh
+                    .material
+                    .map_or(&default_material, |i| &object.materials[i]);
+                let buffer = Buffer::from_data(
+                    memory_allocator.clone(),
+                    BufferCreateInfo {
+                        usage: BufferUsage::UNIFORM_BUFFER,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                            | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                        ..Default::default()
+                    },
+                    fs::MaterialData {
+                        ambient: material.ambient.into(),
+                        diffuse: material.diffuse.into(),
+                        specular: material.specular,
+                        shininess: material.shininess,
+                        dissolve: material.dissolve,
+                        specular_highlight: (material.illum >= 2).into(),
+                    },
+                )?;
+                let texture = material
+                    .diffuse_map
+                    .as_ref()
+                    .map(|map| upload_texture(&memory_allocator, &mut uploads, map));
+
+                Ok((buffer, texture))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
         let sampler = Sampler::new(
             device.clone(),
@@ -260,6 +304,7 @@ impl App {
 
         let rcx = None;
 
+        let camera = Camera::framing(&object, &settings);
         Ok(Self {
             instance,
             device,
@@ -270,9 +315,13 @@ impl App {
             uniform_buffer_allocator,
             vertex_buffer,
             index_buffer,
+            point_size,
             object,
             texture,
+            materials,
             sampler,
+            settings,
+            camera,
             rcx,
         })
     }
@@ -377,6 +426,7 @@ impl ApplicationHandler for App {
             &self.memory_allocator,
             &vs,
             &fs,
+            self.object.topology,
         );
 
         let recreate_swapchain = false;
@@ -391,20 +441,16 @@ impl ApplicationHandler for App {
             pipeline,
             recreate_swapchain,
             previous_frame_end,
-            camera: Camera {
-                target: self.object.center,
-                distance: 5.0
-                    * f32::max(
-                        self.object.size.x,
-                        f32::max(self.object.size.y, self.object.size.z),
-                    ),
-                ..Default::default()
-            },
-            light: Light::default(),
-            input_state: InputState::new(),
+            camera: self.camera.clone(),
+            light: Light::new(&self.settings),
+            input_state: InputState::new(self.settings.controls.clone()),
             time_info: TimeInfo::default(),
             use_texture: false,
-        })
+            selected_group: 0,
+            hidden_groups: vec![false; self.object.groups.len()],
+        });
+
+        self.object.print_groups();
     }
 
     // this is the main loop of the window
@@ -429,7 +475,7 @@ impl ApplicationHandler for App {
                     return;
                 }
 
-                rcx.update_state_after_inputs(&self.object);
+                rcx.update_state_after_inputs(&self.object, &self.settings, &self.camera);
                 rcx.previous_frame_end.as_mut().unwrap().cleanup_finished();
 
                 if rcx.recreate_swapchain {
@@ -449,53 +495,11 @@ impl ApplicationHandler for App {
                         &self.memory_allocator,
                         &rcx.vs,
                         &rcx.fs,
+                        self.object.topology,
                     );
                     rcx.recreate_swapchain = false;
                 }
 
-                // creating the uniform buffer to pass to the shaders
-                let uniform_buffer = {
-                    let aspect_ratio = rcx.swapchain.image_extent()[0] as f32
-                        / rcx.swapchain.image_extent()[1] as f32;
-
-                    let camera = &rcx.camera;
-                    let light = &rcx.light;
-
-                    let proj = Mat4::perspective(0.8, aspect_ratio, 1.0, 10000.0);
-
-                    let uniform_data = vs::Data {
-                        world: Mat4::identity().0,
-                        view: (camera.direction_view_matrix(camera.target_dir())).0,
-                        proj: proj.0,
-                        light_pos: light.position.to_array().into(),
-                        light_color: (light.colors[light.color.0] * light.color.1)
-                            .to_array()
-                            .into(),
-                        ambient_light_color: (light.colors[0] * light.ambient_color.1)
-                            .to_array()
-                            .into(),
-                        texture: rcx.use_texture.into(),
-                    };
-
-                    let buffer = self.uniform_buffer_allocator.allocate_sized().unwrap();
-                    *buffer.write().unwrap() = uniform_data;
-
-                    buffer
-                };
-
-                let layout = &rcx.pipeline.layout().set_layouts()[0];
-                let descriptor_set = DescriptorSet::new(
-                    self.descriptor_set_allocator.clone(),
-                    layout.clone(),
-                    [
-                        WriteDescriptorSet::buffer(0, uniform_buffer),
-                        WriteDescriptorSet::sampler(1, self.sampler.clone()),
-                        WriteDescriptorSet::image_view(2, self.texture.clone()),
-                    ],
-                    [],
-                )
-                .unwrap();
-
                 let (image_index, suboptimal, acquire_future) = match acquire_next_image(
                     rcx.swapchain.clone(),
                     None,
@@ -514,6 +518,13 @@ impl ApplicationHandler for App {
                     rcx.recreate_swapchain = true;
                 }
 
+                // the frame is recorded while the render context is only read
+                let rcx = self.rcx.as_ref().unwrap();
+                let aspect_ratio =
+                    rcx.swapchain.image_extent()[0] as f32 / rcx.swapchain.image_extent()[1] as f32;
+                let uniform_buffer =
+                    self.uniform_buffer(&rcx.camera, &rcx.light, rcx.use_texture, aspect_ratio);
+
                 let mut builder = AutoCommandBufferBuilder::primary(
                     self.command_buffer_allocator.clone(),
                     self.queue.queue_family_index(),
@@ -521,47 +532,16 @@ impl ApplicationHandler for App {
                 )
                 .unwrap();
 
-                builder
-                    .begin_render_pass(
-                        RenderPassBeginInfo {
-                            clear_values: vec![
-                                Some(
-                                    [
-                                        BG_COLOR.0 / 255.0,
-                                        BG_COLOR.1 / 255.0,
-                                        BG_COLOR.2 / 255.0,
-                                        1.0,
-                                    ]
-                                    .into(),
-                                ),
-                                Some(1f32.into()),
-                            ],
-                            ..RenderPassBeginInfo::framebuffer(
-                                rcx.framebuffers[image_index as usize].clone(),
-                            )
-                        },
-                        Default::default(),
-                    )
-                    .unwrap()
-                    .bind_pipeline_graphics(rcx.pipeline.clone())
-                    .unwrap()
-                    .bind_descriptor_sets(
-                        PipelineBindPoint::Graphics,
-                        rcx.pipeline.layout().clone(),
-                        0,
-                        descriptor_set,
-                    )
-                    .unwrap()
-                    .bind_vertex_buffers(0, self.vertex_buffer.clone())
-                    .unwrap()
-                    .bind_index_buffer(self.index_buffer.clone())
-                    .unwrap();
-                unsafe { builder.draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0) }
-                    .unwrap();
-
-                builder.end_render_pass(Default::default()).unwrap();
+                self.record_draw(
+                    &mut builder,
+                    &rcx.pipeline,
+                    rcx.framebuffers[image_index as usize].clone(),
+                    uniform_buffer,
+                    &rcx.hidden_groups,
+                );
 
                 let command_buffer = builder.build().unwrap();
+                let rcx = self.rcx.as_mut().unwrap();
                 let future = rcx
                     .previous_frame_end
                     .take()
@@ -594,8 +574,15 @@ impl ApplicationHandler for App {
 
                 rcx.update_time();
                 rcx.input_state.reset();
+                let group = match self.object.groups.get(rcx.selected_group) {
+                    Some(group) if rcx.hidden_groups[rcx.selected_group] => {
+                        format!(" | group: {} (hidden)", group.name)
+                    }
+                    Some(group) => format!(" | group: {}", group.name),
+                    None => String::new(),
+                };
                 rcx.window
-                    .set_title(&format!("Scop! fps: {:.2}", rcx.avg_fps()));
+                    .set_title(&format!("Scop! fps: {:.2}{group}", rcx.avg_fps()));
             }
             _ => {
                 rcx.input_state
@@ -603,7 +590,13 @@ impl ApplicationHandler for App {
             }
         }
 
-        if rcx.input_state.btn_quit {
+        if self
+            .rcx
+            .as_ref()
+            .unwrap()
+            .input_state
+            .was_pressed(Action::Quit)
+        {
             event_loop.exit();
         }
     }
@@ -614,14 +607,128 @@ impl ApplicationHandler for App {
     }
 }
 
+impl App {
+    // the uniform buffer passed to the shaders for a frame seen from `camera`
+    pub(super) fn uniform_buffer(
+        &self,
+        camera: &Camera,
+        light: &Light,
+        use_texture: bool,
+        aspect_ratio: f32,
+    ) -> Subbuffer<vs::Data> {
+        let settings = &self.settings;
+        let proj = Mat4::perspective(settings.fov, aspect_ratio, settings.near, settings.far);
+
+        let uniform_data = vs::Data {
+            world: Mat4::identity().0,
+            view: (camera.direction_view_matrix(camera.target_dir())).0,
+            proj: proj.0,
+            light_pos: light.position.to_array().into(),
+            light_color: (light.colors[light.color.0] * light.color.1)
+                .to_array()
+                .into(),
+            ambient_light_color: (light.colors[0] * light.ambient_color.1).to_array().into(),
+            texture: use_texture.into(),
+            camera_pos: camera.position.to_array(),
+            point_size: self.point_size,
+        };
+
+        let buffer = self.uniform_buffer_allocator.allocate_sized().unwrap();
+        *buffer.write().unwrap() = uniform_data;
+
+        buffer
+    }
+
+    // records the drawing of the groups which are not hidden into `framebuffer`
+    pub(super) fn record_draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: &Arc<GraphicsPipeline>,
+        framebuffer: Arc<Framebuffer>,
+        uniform_buffer: Subbuffer<vs::Data>,
+        hidden_groups: &[bool],
+    ) {
+        let layout = &pipeline.layout().set_layouts()[0];
+        let descriptor_sets = self
+            .materials
+            .iter()
+            .map(|(material_buffer, texture)| {
+                DescriptorSet::new(
+                    self.descriptor_set_allocator.clone(),
+                    layout.clone(),
+                    [
+                        WriteDescriptorSet::buffer(0, uniform_buffer.clone()),
+                        WriteDescriptorSet::sampler(1, self.sampler.clone()),
+                        WriteDescriptorSet::image_view(
+                            2,
+                            texture.as_ref().unwrap_or(&self.texture).clone(),
+                        ),
+                        WriteDescriptorSet::buffer(3, material_buffer.clone()),
+                    ],
+                    [],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let (r, g, b) = self.settings.bg_color;
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![
+                        Some([r / 255.0, g / 255.0, b / 255.0, 1.0].into()),
+                        Some(1f32.into()),
+                    ],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                Default::default(),
+            )
+            .unwrap()
+            .bind_pipeline_graphics(pipeline.clone())
+            .unwrap()
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .unwrap();
+        if let Some(index_buffer) = &self.index_buffer {
+            builder.bind_index_buffer(index_buffer.clone()).unwrap();
+        }
+        for (submesh, descriptor_set) in self
+            .object
+            .submeshes
+            .iter()
+            .zip(descriptor_sets)
+            .filter(|(submesh, _)| !hidden_groups[submesh.group])
+        {
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    pipeline.layout().clone(),
+                    0,
+                    descriptor_set,
+                )
+                .unwrap();
+            match self.object.topology {
+                Topology::Triangles => unsafe {
+                    builder.draw_indexed(submesh.count, 1, submesh.start, 0, 0)
+                },
+                Topology::Points => unsafe { builder.draw(submesh.count, 1, submesh.start, 0) },
+            }
+            .unwrap();
+        }
+
+        builder.end_render_pass(Default::default()).unwrap();
+    }
+}
+
 // this function creates the framebuffers and the graphics pipeline, it is called when we create the window and when we resize it
-fn window_size_dependent_setup(
+// point clouds are drawn with a point list pipeline, everything else with a triangle list one
+pub(super) fn window_size_dependent_setup(
     window_size: PhysicalSize<u32>,
     images: &[Arc<Image>],
     render_pass: &Arc<RenderPass>,
     memory_allocator: &Arc<StandardMemoryAllocator>,
     vs: &EntryPoint,
     fs: &EntryPoint,
+    topology: Topology,
 ) -> (Vec<Arc<Framebuffer>>, Arc<GraphicsPipeline>) {
     let device = memory_allocator.device();
 
@@ -678,7 +785,13 @@ fn window_size_dependent_setup(
             GraphicsPipelineCreateInfo {
                 stages: stages.into_iter().collect(),
                 vertex_input_state: Some(vertex_input_state),
-                input_assembly_state: Some(InputAssemblyState::default()),
+                input_assembly_state: Some(InputAssemblyState {
+                    topology: match topology {
+                        Topology::Triangles => PrimitiveTopology::TriangleList,
+                        Topology::Points => PrimitiveTopology::PointList,
+                    },
+                    ..Default::default()
+                }),
                 viewport_state: Some(ViewportState {
                     viewports: [Viewport {
                         offset: [0.0, 0.0],
@@ -697,7 +810,10 @@ fn window_size_dependent_setup(
                 multisample_state: Some(MultisampleState::default()),
                 color_blend_state: Some(ColorBlendState::with_attachment_states(
                     subpass.num_color_attachments(),
-                    ColorBlendAttachmentState::default(),
+                    ColorBlendAttachmentState {
+                        blend: Some(AttachmentBlend::alpha()),
+                        ..Default::default()
+                    },
                 )),
                 subpass: Some(subpass.into()),
                 ..GraphicsPipelineCreateInfo::layout(layout)
@@ -709,14 +825,69 @@ fn window_size_dependent_setup(
     (framebuffers, pipeline)
 }
 
-mod vs {
+// copies the texture into a new device image, the copy is recorded in `uploads`
+fn upload_texture(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    uploads: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    texture: &Texture,
+) -> Arc<ImageView> {
+    let format = Format::R8G8B8A8_UNORM;
+    let extent: [u32; 3] = [texture.width, texture.height, 1];
+
+    let upload_buffer = Buffer::new_slice(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        (texture.width * texture.height * 4) as DeviceSize,
+    )
+    .unwrap();
+
+    {
+        let buf = &mut upload_buffer.write().unwrap();
+
+        for i in 0..(texture.width * texture.height * 4) as usize {
+            buf[i] = texture.data[i]
+        }
+    }
+
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format,
+            extent,
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+    .unwrap();
+
+    uploads
+        .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            upload_buffer,
+            image.clone(),
+        ))
+        .unwrap();
+
+    ImageView::new_default(image).unwrap()
+}
+
+pub(super) mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
         path: "src/shaders/vertex.glsl"
     }
 }
 
-mod fs {
+pub(super) mod fs {
     vulkano_shaders::shader! {
         ty: "fragment",
         path: "src/shaders/fragment.glsl"
@@ -1,92 +1,343 @@
 use crate::object_loader::Object;
 
-use super::{Camera, Light, RenderContext};
-use std::{f32::consts, time::Instant};
+use super::{Camera, Light, RenderContext, Settings};
+use std::{error::Error, f32::consts, path::Path, time::Instant};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
-    keyboard::{Key, NamedKey},
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
 };
 
+// what the controls do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ZoomIn,
+    ZoomOut,
+    RotateLeft,
+    RotateRight,
+    MoveUp,
+    MoveDown,
+    Orbit,
+    LockLight,
+    LightColor,
+    Texture,
+    Reset,
+    NextGroup,
+    HideGroup,
+    IsolateGroup,
+    ShowGroups,
+    Export,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Self::ZoomIn,
+        Self::ZoomOut,
+        Self::RotateLeft,
+        Self::RotateRight,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::Orbit,
+        Self::LockLight,
+        Self::LightColor,
+        Self::Texture,
+        Self::Reset,
+        Self::NextGroup,
+        Self::HideGroup,
+        Self::IsolateGroup,
+        Self::ShowGroups,
+        Self::Export,
+        Self::Quit,
+    ];
+
+    // the name of the action in the config
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ZoomIn => "zoom_in",
+            Self::ZoomOut => "zoom_out",
+            Self::RotateLeft => "rotate_left",
+            Self::RotateRight => "rotate_right",
+            Self::MoveUp => "move_up",
+            Self::MoveDown => "move_down",
+            Self::Orbit => "orbit",
+            Self::LockLight => "lock_light",
+            Self::LightColor => "light_color",
+            Self::Texture => "texture",
+            Self::Reset => "reset",
+            Self::NextGroup => "next_group",
+            Self::HideGroup => "hide_group",
+            Self::IsolateGroup => "isolate_group",
+            Self::ShowGroups => "show_groups",
+            Self::Export => "export",
+            Self::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+// what triggers an action: a key by what it types, which depends on the layout, a key by its
+// position on the keyboard, or a mouse button
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Binding {
+    Key(Key),
+    Physical(KeyCode),
+    Mouse(MouseButton),
+}
+
+// the movements are bound by position, so that they stay under the same fingers on any
+// layout, and the toggles by the letter they start with
+pub fn default_controls() -> Vec<(Binding, Action)> {
+    let character = |c: &str| Binding::Key(Key::Character(c.into()));
+    vec![
+        (Binding::Physical(KeyCode::KeyW), Action::ZoomIn),
+        (Binding::Physical(KeyCode::KeyS), Action::ZoomOut),
+        (Binding::Physical(KeyCode::KeyA), Action::RotateLeft),
+        (Binding::Physical(KeyCode::KeyD), Action::RotateRight),
+        (Binding::Key(Key::Named(NamedKey::Space)), Action::MoveUp),
+        (Binding::Key(Key::Named(NamedKey::Shift)), Action::MoveDown),
+        (Binding::Mouse(MouseButton::Left), Action::Orbit),
+        (character("l"), Action::LockLight),
+        (character("c"), Action::LightColor),
+        (character("t"), Action::Texture),
+        (character("r"), Action::Reset),
+        (character("g"), Action::NextGroup),
+        (character("h"), Action::HideGroup),
+        (character("i"), Action::IsolateGroup),
+        (character("u"), Action::ShowGroups),
+        (character("e"), Action::Export),
+        (Binding::Key(Key::Named(NamedKey::Escape)), Action::Quit),
+    ]
+}
+
+// a control of the config: a character or the name of a key such as space or escape, either
+// of them after physical: to bind the key at this place of a qwerty keyboard, or mouse: and a
+// button
+pub fn parse_binding(name: &str) -> Result<Binding, Box<dyn Error>> {
+    if let Some(button) = name.strip_prefix("mouse:") {
+        let button = match button.to_lowercase().as_str() {
+            "left" => MouseButton::Left,
+            "right" => MouseButton::Right,
+            "middle" => MouseButton::Middle,
+            "back" => MouseButton::Back,
+            "forward" => MouseButton::Forward,
+            _ => return Err(format!("unknown mouse button {button}").into()),
+        };
+        return Ok(Binding::Mouse(button));
+    }
+    if let Some(key) = name.strip_prefix("physical:") {
+        return physical_key(key).map(Binding::Physical);
+    }
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(Binding::Key(Key::Character(
+            c.to_lowercase().to_string().into(),
+        )));
+    }
+    let named = match name.to_lowercase().as_str() {
+        "space" => NamedKey::Space,
+        "shift" => NamedKey::Shift,
+        "control" | "ctrl" => NamedKey::Control,
+        "alt" => NamedKey::Alt,
+        "escape" | "esc" => NamedKey::Escape,
+        "enter" => NamedKey::Enter,
+        "tab" => NamedKey::Tab,
+        "backspace" => NamedKey::Backspace,
+        "delete" => NamedKey::Delete,
+        "up" => NamedKey::ArrowUp,
+        "down" => NamedKey::ArrowDown,
+        "left" => NamedKey::ArrowLeft,
+        "right" => NamedKey::ArrowRight,
+        "pageup" => NamedKey::PageUp,
+        "pagedown" => NamedKey::PageDown,
+        "home" => NamedKey::Home,
+        "end" => NamedKey::End,
+        _ => return Err(format!("unknown key {name}").into()),
+    };
+    Ok(Binding::Key(Key::Named(named)))
+}
+
+// the modifiers have a key on each side, the left one is bound
+fn physical_key(name: &str) -> Result<KeyCode, Box<dyn Error>> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    let name = name.to_lowercase();
+    if let [c] = name.as_bytes() {
+        match c {
+            b'a'..=b'z' => return Ok(LETTERS[(c - b'a') as usize]),
+            b'0'..=b'9' => return Ok(DIGITS[(c - b'0') as usize]),
+            _ => {}
+        }
+    }
+    Ok(match name.as_str() {
+        "space" => KeyCode::Space,
+        "shift" => KeyCode::ShiftLeft,
+        "control" | "ctrl" => KeyCode::ControlLeft,
+        "alt" => KeyCode::AltLeft,
+        "escape" | "esc" => KeyCode::Escape,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::ArrowUp,
+        "down" => KeyCode::ArrowDown,
+        "left" => KeyCode::ArrowLeft,
+        "right" => KeyCode::ArrowRight,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        _ => return Err(format!("unknown physical key {name}").into()),
+    })
+}
+
 pub struct InputState {
+    pub controls: Vec<(Binding, Action)>,
+    // the keys and buttons held down, each by all the bindings it was pressed with
+    held: Vec<Vec<Binding>>,
+    // the actions pressed since the last frame
+    pressed: Vec<Action>,
     pub window_size: [f32; 2],
     pub mouse_pos: [f32; 2],
     pub mouse_delta: [f32; 2],
     pub mouse_scroll_delta: f32,
-    pub mouse_left_click: bool,
-    pub mouse_right_click: bool,
-    pub btn_zoom_in: bool,
-    pub btn_zoom_out: bool,
-    pub btn_rotate_left: bool,
-    pub btn_rotate_right: bool,
-    pub btn_move_up: bool,
-    pub btn_move_down: bool,
-    pub btn_lock_light: bool,
-    pub btn_light_color: bool,
-    pub btn_texture: bool,
-    pub btn_reset: bool,
-    pub btn_quit: bool,
 }
 
 impl InputState {
-    pub fn new() -> Self {
+    pub fn new(controls: Vec<(Binding, Action)>) -> Self {
         Self {
+            controls,
+            held: Vec::new(),
+            pressed: Vec::new(),
             window_size: [0.0, 0.0],
             mouse_pos: [0.0, 0.0],
             mouse_delta: [0.0, 0.0],
             mouse_scroll_delta: 0.0,
-            mouse_left_click: false,
-            mouse_right_click: false,
-            btn_zoom_in: false,
-            btn_zoom_out: false,
-            btn_rotate_left: false,
-            btn_rotate_right: false,
-            btn_move_up: false,
-            btn_move_down: false,
-            btn_lock_light: false,
-            btn_light_color: false,
-            btn_texture: false,
-            btn_reset: false,
-            btn_quit: false,
         }
     }
 
+    // whether a binding of the action is held down
+    pub fn is_held(&self, action: Action) -> bool {
+        self.controls.iter().any(|(binding, bound)| {
+            *bound == action && self.held.iter().any(|held| held.contains(binding))
+        })
+    }
+
+    // whether the action was pressed since the last frame, once however long it is held
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
     pub fn handle_input(&mut self, window_size: PhysicalSize<u32>, event: &WindowEvent) {
         self.window_size = window_size.into();
 
         match event {
             WindowEvent::KeyboardInput { event, .. } => self.on_keyboard_event(event),
             WindowEvent::MouseInput { state, button, .. } => {
-                self.on_mouse_click_event(*state, *button)
+                self.on_binding_event(&[Binding::Mouse(*button)], *state, false)
             }
             WindowEvent::CursorMoved { position, .. } => self.on_cursor_moved_event(position),
             WindowEvent::MouseWheel { delta, .. } => self.on_mouse_wheel_event(delta),
+            // the releases are missed while the window is not focused
+            WindowEvent::Focused(false) => self.held.clear(),
             _ => {}
         }
     }
 
     fn on_keyboard_event(&mut self, event: &KeyEvent) {
-        match event.logical_key.as_ref() {
-            Key::Character("w") => self.btn_zoom_in = event.state.is_pressed(),
-            Key::Character("s") => self.btn_zoom_out = event.state.is_pressed(),
-            Key::Character("a") => self.btn_rotate_left = event.state.is_pressed(),
-            Key::Character("d") => self.btn_rotate_right = event.state.is_pressed(),
-            Key::Character("l") => self.btn_lock_light = event.state.is_pressed(),
-            Key::Character("c") => self.btn_light_color = event.state.is_pressed(),
-            Key::Character("r") => self.btn_reset = event.state.is_pressed(),
-            Key::Character("t") => self.btn_texture = event.state.is_pressed(),
-            Key::Named(NamedKey::Space) => self.btn_move_up = event.state.is_pressed(),
-            Key::Named(NamedKey::Shift) => self.btn_move_down = event.state.is_pressed(),
-            Key::Named(NamedKey::Escape) => self.btn_quit = event.state.is_pressed(),
-            _ => {}
+        self.on_key_event(
+            &event.logical_key,
+            event.physical_key,
+            event.state,
+            event.repeat,
+        );
+    }
+
+    // the fields of a KeyEvent, which can't be built outside of winit
+    fn on_key_event(
+        &mut self,
+        logical_key: &Key,
+        physical_key: PhysicalKey,
+        state: ElementState,
+        repeat: bool,
+    ) {
+        // the characters are bound in lowercase, whether shift is held or not
+        let key = match logical_key {
+            Key::Character(c) => Key::Character(c.to_lowercase().into()),
+            key => key.clone(),
+        };
+        let mut bindings = vec![Binding::Key(key)];
+        if let PhysicalKey::Code(code) = physical_key {
+            bindings.push(Binding::Physical(code));
         }
+        self.on_binding_event(&bindings, state, repeat);
     }
 
-    fn on_mouse_click_event(&mut self, state: ElementState, mouse_btn: MouseButton) {
-        match mouse_btn {
-            MouseButton::Left => self.mouse_left_click = state.is_pressed(),
-            MouseButton::Right => self.mouse_right_click = state.is_pressed(),
-            _ => {}
+    fn on_binding_event(&mut self, bindings: &[Binding], state: ElementState, repeat: bool) {
+        // the logical key may have changed since it was pressed, when a modifier was, so the
+        // physical one releases all the bindings of the press
+        if !state.is_pressed() {
+            self.held
+                .retain(|held| !held.iter().any(|binding| bindings.contains(binding)));
+            return;
+        }
+        if !self.held.iter().any(|held| held == bindings) {
+            self.held.push(bindings.to_vec());
+        }
+        if repeat {
+            return;
+        }
+        for (_, action) in self
+            .controls
+            .iter()
+            .filter(|(binding, _)| bindings.contains(binding))
+        {
+            if !self.pressed.contains(action) {
+                self.pressed.push(*action);
+            }
         }
     }
 
@@ -113,15 +364,17 @@ impl InputState {
     pub fn reset(&mut self) {
         self.mouse_delta = [0.0, 0.0];
         self.mouse_scroll_delta = 0.0;
-        self.btn_lock_light = false;
-        self.btn_light_color = false;
-        self.btn_texture = false;
-        self.btn_reset = false;
+        self.pressed.clear();
     }
 }
 
 impl RenderContext {
-    pub fn update_state_after_inputs(&mut self, object: &Object) {
+    pub fn update_state_after_inputs(
+        &mut self,
+        object: &Object,
+        settings: &Settings,
+        start: &Camera,
+    ) {
         let state = &self.input_state;
         let time = &self.time_info;
         let camera = &mut self.camera;
@@ -129,28 +382,28 @@ impl RenderContext {
 
         let camera_speed = time.dt * object.size.length();
 
-        if state.btn_zoom_in {
+        if state.is_held(Action::ZoomIn) {
             camera.distance -= camera_speed;
             if camera.distance < 0.0 {
                 camera.distance = 0.0;
             }
         }
-        if state.btn_zoom_out {
+        if state.is_held(Action::ZoomOut) {
             camera.distance += camera_speed;
         }
-        if state.btn_rotate_left {
+        if state.is_held(Action::RotateLeft) {
             camera.theta = (camera.theta - consts::PI * time.dt) % (2.0 * consts::PI);
         }
-        if state.btn_rotate_right {
+        if state.is_held(Action::RotateRight) {
             camera.theta = (camera.theta + consts::PI * time.dt) % (2.0 * consts::PI);
         }
-        if state.btn_move_up {
+        if state.is_held(Action::MoveUp) {
             camera.target.y += camera_speed;
         }
-        if state.btn_move_down {
+        if state.is_held(Action::MoveDown) {
             camera.target.y -= camera_speed;
         }
-        if state.mouse_left_click {
+        if state.is_held(Action::Orbit) {
             camera.theta += -state.mouse_delta[0] * 10.0;
             camera.phi += -state.mouse_delta[1] * 10.0;
             camera.phi = f32::max(
@@ -161,28 +414,54 @@ impl RenderContext {
         if state.mouse_scroll_delta != 0.0 {
             camera.distance += -state.mouse_scroll_delta;
         }
-        if state.btn_lock_light {
+        if state.was_pressed(Action::LockLight) {
             light.pos_locked = !light.pos_locked;
         }
-        if state.btn_light_color {
+        if state.was_pressed(Action::LightColor) {
             light.color.0 = (light.color.0 + 1) % light.colors.len();
         }
-        if state.btn_texture {
+        if state.was_pressed(Action::Texture) {
             self.use_texture = !self.use_texture;
         }
+        if state.was_pressed(Action::NextGroup) && !object.groups.is_empty() {
+            self.selected_group = (self.selected_group + 1) % object.groups.len();
+            println!(
+                "selected group {}: {}",
+                self.selected_group, object.groups[self.selected_group].name
+            );
+        }
+        if state.was_pressed(Action::HideGroup) && !object.groups.is_empty() {
+            self.hidden_groups[self.selected_group] = !self.hidden_groups[self.selected_group];
+        }
+        if state.was_pressed(Action::IsolateGroup) && !object.groups.is_empty() {
+            // isolating the only visible group shows everything again
+            let isolated = self
+                .hidden_groups
+                .iter()
+                .enumerate()
+                .all(|(i, &hidden)| hidden == (i != self.selected_group));
+            for (i, hidden) in self.hidden_groups.iter_mut().enumerate() {
+                *hidden = !isolated && i != self.selected_group;
+            }
+        }
+        if state.was_pressed(Action::ShowGroups) {
+            self.hidden_groups.fill(false);
+        }
+        if state.was_pressed(Action::Export) {
+            match object.export(Path::new(EXPORT_OBJ)) {
+                Ok(()) => println!("object exported to {EXPORT_OBJ}"),
+                Err(e) => println!("failed to export the object: {e}"),
+            }
+        }
 
         camera.update_position();
         if !light.pos_locked {
             light.position = camera.position;
         }
 
-        if state.btn_reset {
-            self.camera = Camera {
-                target: object.center,
-                distance: 5.0 * f32::max(object.size.x, f32::max(object.size.y, object.size.z)),
-                ..Default::default()
-            };
-            self.light = Light::default();
+        if state.was_pressed(Action::Reset) {
+            self.camera = start.clone();
+            self.light = Light::new(settings);
         }
     }
 
@@ -206,3 +485,81 @@ impl RenderContext {
         time.time = Instant::now();
     }
 }
+
+// written in the working directory by the export key, with scop_export.mtl
+const EXPORT_OBJ: &str = "scop_export.obj";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn bindings() {
+        assert_eq!(
+            parse_binding("physical:w").unwrap(),
+            Binding::Physical(KeyCode::KeyW)
+        );
+        assert_eq!(
+            parse_binding("physical:Shift").unwrap(),
+            Binding::Physical(KeyCode::ShiftLeft)
+        );
+        assert_eq!(
+            parse_binding("mouse:left").unwrap(),
+            Binding::Mouse(MouseButton::Left)
+        );
+        assert_eq!(
+            parse_binding("esc").unwrap(),
+            Binding::Key(Key::Named(NamedKey::Escape))
+        );
+        // the characters are bound in lowercase, as the events are matched
+        assert_eq!(parse_binding("Q").unwrap(), Binding::Key(character("q")));
+        assert_eq!(parse_binding("é").unwrap(), Binding::Key(character("é")));
+        for name in ["mouse:side", "physical:hyper", "physical:", "hyper", ""] {
+            assert!(parse_binding(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn repeat() {
+        let mut state = InputState::new(default_controls());
+        let e = PhysicalKey::Code(KeyCode::KeyE);
+        state.on_key_event(&character("e"), e, ElementState::Pressed, false);
+        assert!(state.was_pressed(Action::Export));
+        state.reset();
+        // the repeats of the system while the key is held
+        for _ in 0..3 {
+            state.on_key_event(&character("e"), e, ElementState::Pressed, true);
+            assert!(!state.was_pressed(Action::Export));
+            state.reset();
+        }
+        state.on_key_event(&character("e"), e, ElementState::Released, false);
+        state.on_key_event(&character("e"), e, ElementState::Pressed, false);
+        assert!(state.was_pressed(Action::Export));
+    }
+
+    #[test]
+    fn modifier_changes_key() {
+        let controls = vec![
+            (Binding::Key(character("1")), Action::NextGroup),
+            (Binding::Physical(KeyCode::KeyW), Action::ZoomIn),
+        ];
+        let mut state = InputState::new(controls);
+        let digit = PhysicalKey::Code(KeyCode::Digit1);
+        state.on_key_event(&character("1"), digit, ElementState::Pressed, false);
+        assert!(state.is_held(Action::NextGroup));
+        // shift is pressed while the key is held, so it is released as !
+        state.on_key_event(&character("!"), digit, ElementState::Released, false);
+        assert!(!state.is_held(Action::NextGroup));
+
+        // the same for a key bound by its position
+        let w = PhysicalKey::Code(KeyCode::KeyW);
+        state.on_key_event(&character("w"), w, ElementState::Pressed, false);
+        assert!(state.is_held(Action::ZoomIn));
+        state.on_key_event(&character("W"), w, ElementState::Released, false);
+        assert!(!state.is_held(Action::ZoomIn));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod app;
 pub mod input;
+pub mod render;
 pub mod view;
 
 use crate::{
@@ -7,7 +8,7 @@ use crate::{
     object_loader::{Object, Vertexxx},
     vec3,
 };
-use input::InputState;
+use input::{Action, Binding, InputState};
 use std::{sync::Arc, time::Instant};
 use vulkano::{
     buffer::{allocator::SubbufferAllocator, Subbuffer},
@@ -34,13 +35,47 @@ pub struct App {
     pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
     pub uniform_buffer_allocator: SubbufferAllocator,
     pub vertex_buffer: Subbuffer<[Vertexxx]>,
-    pub index_buffer: Subbuffer<[u32]>,
+    pub index_buffer: Option<Subbuffer<[u32]>>,
+    pub point_size: f32,
     pub object: Object,
     pub texture: Arc<ImageView>,
+    pub materials: Vec<(Subbuffer<app::fs::MaterialData>, Option<Arc<ImageView>>)>,
     pub sampler: Arc<Sampler>,
+    pub settings: Settings,
+    // the camera the object is first seen from, and that the reset key goes back to
+    pub camera: Camera,
     pub rcx: Option<RenderContext>,
 }
 
+// how the object is rendered and controlled, as set by the config and the command line
+#[derive(Debug, Clone)]
+pub struct Settings {
+    // the vertical field of view, in radians
+    pub fov: f32,
+    // the distances of the clipping planes
+    pub near: f32,
+    pub far: f32,
+    // how far the camera starts from the object, in times its biggest side
+    pub camera_distance: f32,
+    // from 0 to 255
+    pub bg_color: (f32, f32, f32),
+    // the colors the light goes through, the first one is also the one of the ambient light
+    pub light_colors: Vec<Vec3>,
+    pub light_intensity: f32,
+    pub ambient_intensity: f32,
+    // in pixels, for point clouds
+    pub point_size: f32,
+    // faces of models without normals nor smoothing groups are smoothed with their
+    // neighbours less than this angle (in radians) away, they stay flat without it
+    pub crease_angle: Option<f32>,
+    // of the images of the render command
+    pub render_size: (u32, u32),
+    // the index or a part of the name of the vulkan device to use, the fastest one otherwise
+    pub device: Option<String>,
+    // the keys and mouse buttons of each action
+    pub controls: Vec<(Binding, Action)>,
+}
+
 pub struct RenderContext {
     window: Arc<Window>,
     swapchain: Arc<Swapchain>,
@@ -56,8 +91,11 @@ pub struct RenderContext {
     input_state: InputState,
     time_info: TimeInfo,
     use_texture: bool,
+    selected_group: usize,
+    hidden_groups: Vec<bool>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Camera {
     position: Vec3,
     target: Vec3,
@@ -94,12 +132,15 @@ impl Default for Camera {
     }
 }
 
-impl Default for Light {
+impl Default for Settings {
     fn default() -> Self {
         Self {
-            position: vec3!(0.0, 0.0, 10.0),
-            pos_locked: false,
-            colors: vec![
+            fov: 0.8,
+            near: 1.0,
+            far: 10000.0,
+            camera_distance: 5.0,
+            bg_color: (40.0, 40.0, 40.0),
+            light_colors: vec![
                 vec3!(1.0, 1.0, 1.0),
                 vec3!(1.0, 0.0, 0.0),
                 vec3!(0.0, 1.0, 0.0),
@@ -107,8 +148,25 @@ impl Default for Light {
                 vec3!(1.0, 0.55294117647058823529, 0.63137254901960784313),
                 vec3!(0.2941176471, 0.0, 0.5098039216),
             ],
-            color: (0, 1.0),
-            ambient_color: (0, 0.2),
+            light_intensity: 1.0,
+            ambient_intensity: 0.2,
+            point_size: 3.0,
+            crease_angle: None,
+            render_size: (1280, 720),
+            device: None,
+            controls: input::default_controls(),
+        }
+    }
+}
+
+impl Light {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            position: vec3!(0.0, 0.0, 10.0),
+            pos_locked: false,
+            colors: settings.light_colors.clone(),
+            color: (0, settings.light_intensity),
+            ambient_color: (0, settings.ambient_intensity),
         }
     }
 }
@@ -0,0 +1,132 @@
+use super::{
+    app::{fs, vs, window_size_dependent_setup},
+    App, Light,
+};
+use crate::object_loader::texture::Texture;
+use std::{error::Error, sync::Arc};
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage},
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+        PrimaryCommandBufferAbstract,
+    },
+    format::Format,
+    image::{Image, ImageCreateInfo, ImageType, ImageUsage},
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter},
+    sync::GpuFuture,
+    DeviceSize,
+};
+use winit::dpi::PhysicalSize;
+
+impl App {
+    // renders a frame of `width`x`height` pixels into an image, from where the window first
+    // sees the object, with the light at the camera
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        use_texture: bool,
+    ) -> Result<Texture, Box<dyn Error>> {
+        let format = Format::R8G8B8A8_UNORM;
+        let image = Image::new(
+            self.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format,
+                extent: [width, height, 1],
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+
+        let render_pass = vulkano::single_pass_renderpass!(
+            self.device.clone(),
+            attachments: {
+                color: {
+                    format: format,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                depth_stencil: {
+                    format: Format::D16_UNORM,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: DontCare,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth_stencil},
+            },
+        )?;
+
+        let vs = vs::load(self.device.clone())?
+            .entry_point("main")
+            .ok_or("the vertex shader has no main")?;
+        let fs = fs::load(self.device.clone())?
+            .entry_point("main")
+            .ok_or("the fragment shader has no main")?;
+
+        let (framebuffers, pipeline) = window_size_dependent_setup(
+            PhysicalSize::new(width, height),
+            std::slice::from_ref(&image),
+            &render_pass,
+            &self.memory_allocator,
+            &vs,
+            &fs,
+            self.object.topology,
+        );
+
+        let mut camera = self.camera.clone();
+        camera.update_position();
+        let light = Light {
+            position: camera.position,
+            ..Light::new(&self.settings)
+        };
+        let uniform_buffer =
+            self.uniform_buffer(&camera, &light, use_texture, width as f32 / height as f32);
+
+        let pixels = Buffer::new_slice::<u8>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            (width * height * 4) as DeviceSize,
+        )?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            self.command_buffer_allocator.clone(),
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        self.record_draw(
+            &mut builder,
+            &pipeline,
+            Arc::clone(&framebuffers[0]),
+            uniform_buffer,
+            &vec![false; self.object.groups.len()],
+        );
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, pixels.clone()))?;
+
+        builder
+            .build()?
+            .execute(self.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        let data = pixels.read()?.to_vec();
+        Ok(Texture {
+            width,
+            height,
+            data,
+        })
+    }
+}
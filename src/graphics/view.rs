@@ -1,7 +1,32 @@
-use super::Camera;
-use crate::math::{Mat4, Vec3};
+use super::{Camera, Settings};
+use crate::{
+    math::{Mat4, Vec3},
+    object_loader::Object,
+};
 
 impl Camera {
+    // the camera the object is first seen from, far enough to see all of it
+    pub fn framing(object: &Object, settings: &Settings) -> Self {
+        Self {
+            target: object.center,
+            distance: settings.camera_distance
+                * f32::max(object.size.x, f32::max(object.size.y, object.size.z)),
+            ..Default::default()
+        }
+    }
+
+    // a camera looking at `target` from `distance`, at the horizontal angle `theta` around it
+    // and the vertical angle `phi` above it, in radians. At 0 and 0, it is on the x axis
+    pub fn orbit(target: Vec3, distance: f32, theta: f32, phi: f32) -> Self {
+        Self {
+            target,
+            distance,
+            theta,
+            phi,
+            ..Default::default()
+        }
+    }
+
     pub fn direction_view_matrix(&self, direction: Vec3) -> Mat4 {
         let w = direction.normalize();
         let u = -Vec3::cross(
@@ -0,0 +1,9 @@
+// the loaders of objects and textures, the math and the renderer of scop, with a viewer
+// putting them together for the programs that show or render objects
+pub mod config;
+pub mod graphics;
+pub mod math;
+pub mod object_loader;
+pub mod viewer;
+
+pub use viewer::Viewer;
@@ -1,40 +1,145 @@
-mod graphics;
-mod math;
-mod object_loader;
+mod cli;
 
-use graphics::App;
-use object_loader::texture::Texture;
-use object_loader::Object;
+use cli::{Command, ViewArgs};
+use scop::{
+    config,
+    graphics::Settings,
+    object_loader::{loader, object::ParseOptions, Topology},
+    Viewer,
+};
 use std::env;
-use std::fs;
-use winit::event_loop::EventLoop;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
 
-const BG_COLOR: (f32, f32, f32) = (40.0, 40.0, 40.0);
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        panic!("This program expects 2 arguments");
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("{}", cli::help(None, &Settings::default()));
+        return ExitCode::from(2);
     }
-
-    let object = {
-        let objfile = fs::read_to_string(&args[1]).expect("obj file not found");
-        match Object::parse(&objfile) {
-            Err(e) => panic!("failed to parse the obj file: {e}"),
-            Ok(obj) => obj
+    let command = match cli::parse(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("scop: {e}");
+            eprintln!("run `scop --help` for the usage");
+            return ExitCode::from(2);
         }
     };
 
-    let texture = {
-        let textfile = fs::read_to_string(&args[2]).expect("ppm file not found");
-        match Texture::parse_ppm(&textfile) {
-            Err(e) => panic!("failed to parse the ppm file: {e}"),
-            Ok(tex) => tex
+    let result = match command {
+        Command::Help(command) => {
+            // the defaults are the ones of the config, unless it is invalid
+            let settings = config::load().unwrap_or_default();
+            println!("{}", cli::help(command, &settings));
+            Ok(())
         }
+        Command::View(args) => view(args),
+        Command::Info(path) => info(&path),
+        Command::Convert { input, output } => convert(&input, &output),
+        Command::Render { view, output } => render(view, &output),
     };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("scop: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn view(args: ViewArgs) -> Result<(), Box<dyn Error>> {
+    viewer(args, true)?.run()
+}
+
+fn info(path: &Path) -> Result<(), Box<dyn Error>> {
+    let viewer = Viewer::load(path)?;
+    let object = viewer.object();
+
+    println!(
+        "{}: {} file",
+        path.display(),
+        loader::mesh_loader(path).name()
+    );
+    match object.topology {
+        Topology::Triangles => println!(
+            "{} vertices, {} triangles",
+            object.vertex.len(),
+            object.indice.len() / 3
+        ),
+        Topology::Points => println!("{} points", object.vertex.len()),
+    }
+    let (size, center) = (object.size, object.center);
+    println!("size: {} x {} x {}", size.x, size.y, size.z);
+    println!("center: {} {} {}", center.x, center.y, center.z);
+
+    object.print_groups();
+    println!("materials:");
+    for material in object.materials.iter() {
+        match &material.map_kd {
+            Some(map) => println!("  {} (texture {map})", material.name),
+            None => println!("  {}", material.name),
+        }
+    }
+    Ok(())
+}
 
-    let event_loop = EventLoop::new().unwrap();
-    let mut app = App::new(&event_loop, object, texture).unwrap();
+fn convert(input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let viewer = Viewer::load(input)?;
+    viewer
+        .object()
+        .export(output)
+        .map_err(|e| format!("failed to write {}: {e}", output.display()))?;
+    println!("{} converted to {}", input.display(), output.display());
+    Ok(())
+}
+
+fn render(args: ViewArgs, output: &Path) -> Result<(), Box<dyn Error>> {
+    let object = args.object.clone();
+    let viewer = viewer(args, false)?;
+    let (width, height) = viewer.settings().render_size;
+    let image = viewer.render(width, height)?;
 
-    event_loop.run_app(&mut app).unwrap();
+    let write = || {
+        let mut out = BufWriter::new(File::create(output)?);
+        image.write_ppm(&mut out)?;
+        out.flush()
+    };
+    write().map_err(|e| format!("failed to write {}: {e}", output.display()))?;
+    println!("{} rendered to {}", object.display(), output.display());
+    Ok(())
+}
+
+// the object and the texture of the arguments, seen with the settings of the config overridden
+// by the ones of the command line. The progress of the loading is only shown in the window
+fn viewer(args: ViewArgs, progress: bool) -> Result<Viewer, Box<dyn Error>> {
+    let mut settings = config::load()?;
+    args.overrides.apply(&mut settings);
+    let options = ParseOptions {
+        crease_angle: settings.crease_angle,
+        ..Default::default()
+    };
+    let mut finished = false;
+    let mut viewer = Viewer::load_with_options(&args.object, &options, |consumed, total| {
+        if progress && !finished {
+            eprint!(
+                "\rloading {}: {}%",
+                args.object.display(),
+                consumed * 100 / total.max(1)
+            );
+            // the line is ended once the whole file is read, before the warnings
+            finished = consumed >= total;
+            if finished {
+                eprintln!();
+            }
+            io::stderr().flush().ok();
+        }
+    })?;
+    if let Some(texture) = &args.texture {
+        viewer.load_texture(texture)?;
+    }
+    viewer.set_settings(settings);
+    Ok(viewer)
 }
@@ -1,3 +1,4 @@
+use super::Vec3;
 use std::ops::{Add, Index, IndexMut, Mul, MulAssign};
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -133,6 +134,40 @@ impl Mat4 {
         rotation_matrix
     }
 
+    // rotation of the unit quaternion [x, y, z, w]
+    pub fn rotate_quaternion([x, y, z, w]: [f32; 4]) -> Self {
+        Self([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // unlike `Vec3 * Mat4`, the translation is applied as well
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        let mut transformed = *point * *self;
+        transformed.x += self[0][3];
+        transformed.y += self[1][3];
+        transformed.z += self[2][3];
+        transformed
+    }
+
     pub fn perspective(vertical_fov: f32, aspect_ratio: f32, z_near: f32, z_far: f32) -> Self {
         let inv_length = 1.0 / (z_near - z_far);
         let f = 1.0 / (0.5 * vertical_fov).tan();
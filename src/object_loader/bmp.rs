@@ -0,0 +1,250 @@
+use super::texture::Texture;
+use std::error::Error;
+
+const FILE_HEADER_SIZE: usize = 14;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+impl Texture {
+    // loads an uncompressed bmp image of 24 or 32 bits per pixel, whose rows go from the
+    // bottom to the top, or from the top when its height is negative. The 32 bits pixels have
+    // an alpha channel only when their masks say so
+    pub fn parse_bmp(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.len() < FILE_HEADER_SIZE + 40 || !data.starts_with(b"BM") {
+            return Err("invalid bmp header".into());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+
+        let offset = u32_at(10) as usize;
+        let header_size = u32_at(14) as usize;
+        if header_size < 40 {
+            return Err("unsupported bmp header, only the ones of 40 bytes or more are".into());
+        }
+        let width = u32_at(18) as i32;
+        let height = u32_at(22) as i32;
+        let bits = u16_at(28);
+        let compression = u32_at(30);
+        if width <= 0 || height == 0 {
+            return Err(format!("the image is {width}x{height}").into());
+        }
+        let (width, top_down) = (width as usize, height < 0);
+        let height = height.unsigned_abs() as usize;
+
+        // the masks of the red, green, blue and alpha channels
+        let masks = match (bits, compression) {
+            (24 | 32, BI_RGB) => [0xff0000, 0xff00, 0xff, 0],
+            (32, BI_BITFIELDS | BI_ALPHABITFIELDS) => {
+                // the masks follow the 40 first bytes of the header, the alpha one is only
+                // there in the bigger headers or with the alpha compression
+                let start = FILE_HEADER_SIZE + 40;
+                let has_alpha = header_size >= 56 || compression == BI_ALPHABITFIELDS;
+                if data.len() < start + 16 {
+                    return Err("the bmp masks are truncated".into());
+                }
+                let alpha = if has_alpha { u32_at(start + 12) } else { 0 };
+                [u32_at(start), u32_at(start + 4), u32_at(start + 8), alpha]
+            }
+            (24 | 32, _) => return Err(format!("unsupported bmp compression {compression}").into()),
+            _ => {
+                return Err(format!(
+                    "unsupported bmp of {bits} bits per pixel, only 24 and 32 are supported"
+                )
+                .into())
+            }
+        };
+
+        let pixel_size = bits as usize / 8;
+        // the rows are padded to 4 bytes
+        let stride = (width * pixel_size).div_ceil(4) * 4;
+        let size = stride
+            .checked_mul(height)
+            .and_then(|size| size.checked_add(offset))
+            .ok_or("the image is too big")?;
+        if data.len() < size {
+            return Err("the pixels are truncated".into());
+        }
+
+        let mut texture = Texture {
+            width: width as u32,
+            height: height as u32,
+            data: Vec::with_capacity(width * height * 4),
+        };
+        for y in 0..height {
+            let row = if top_down { y } else { height - 1 - y };
+            let row = &data[offset + row * stride..][..width * pixel_size];
+            for pixel in row.chunks_exact(pixel_size) {
+                let value = match *pixel {
+                    [b, g, r] => u32::from_le_bytes([b, g, r, 0]),
+                    [b, g, r, a] => u32::from_le_bytes([b, g, r, a]),
+                    _ => unreachable!(),
+                };
+                let mut rgba = masks.map(|mask| channel(value, mask));
+                if masks[3] == 0 {
+                    rgba[3] = 255;
+                }
+                texture.data.extend_from_slice(&rgba);
+            }
+        }
+        Ok(texture)
+    }
+}
+
+// the value of the bits of `mask`, scaled to 8 bits
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = mask.count_ones();
+    let value = (value & mask) >> mask.trailing_zeros();
+    if bits >= 8 {
+        (value >> (bits - 8)) as u8
+    } else {
+        (value * 255 / ((1 << bits) - 1)) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a bmp of `header_size` bytes of header with the masks after its 40 first bytes, and
+    // the `rows` of pixels as they are stored
+    fn bmp(
+        (width, height): (i32, i32),
+        bits: u16,
+        compression: u32,
+        header_size: u32,
+        masks: &[u32],
+        rows: &[&[u8]],
+    ) -> Vec<u8> {
+        let mut info = Vec::new();
+        info.extend_from_slice(&header_size.to_le_bytes());
+        info.extend_from_slice(&width.to_le_bytes());
+        info.extend_from_slice(&height.to_le_bytes());
+        info.extend_from_slice(&1u16.to_le_bytes());
+        info.extend_from_slice(&bits.to_le_bytes());
+        info.extend_from_slice(&compression.to_le_bytes());
+        info.resize(40, 0);
+        for mask in masks {
+            info.extend_from_slice(&mask.to_le_bytes());
+        }
+        info.resize(info.len().max(header_size as usize), 0);
+
+        let offset = FILE_HEADER_SIZE + info.len();
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&(offset as u32).to_le_bytes());
+        data.extend_from_slice(&info);
+        for row in rows {
+            data.extend_from_slice(row);
+        }
+        data
+    }
+
+    #[test]
+    fn bottom_up_and_padding() {
+        // 2x2 bgr rows padded from 6 to 8 bytes, the bottom one first
+        let bottom: &[u8] = &[255, 0, 0, 0, 255, 0, 0, 0];
+        let top: &[u8] = &[0, 0, 255, 1, 2, 3, 0, 0];
+        let data = bmp((2, 2), 24, BI_RGB, 40, &[], &[bottom, top]);
+        let texture = Texture::parse_bmp(&data).unwrap();
+        assert_eq!((texture.width, texture.height), (2, 2));
+        assert_eq!(
+            texture.data,
+            [255, 0, 0, 255, 3, 2, 1, 255, 0, 0, 255, 255, 0, 255, 0, 255]
+        );
+    }
+
+    #[test]
+    fn top_down() {
+        let first: &[u8] = &[1, 2, 3, 4];
+        let second: &[u8] = &[5, 6, 7, 8];
+        let data = bmp((1, -2), 32, BI_RGB, 40, &[], &[first, second]);
+        let texture = Texture::parse_bmp(&data).unwrap();
+        // the fourth byte of BI_RGB pixels is not their alpha
+        assert_eq!(texture.data, [3, 2, 1, 255, 7, 6, 5, 255]);
+    }
+
+    #[test]
+    fn bitfields() {
+        let pixel = 0xaabb_ccdd_u32.to_le_bytes();
+        let rows: &[&[u8]] = &[&pixel];
+
+        // rgba masks in a v4 header
+        let masks = [0xff00_0000, 0x00ff_0000, 0x0000_ff00, 0x0000_00ff];
+        let data = bmp((1, 1), 32, BI_BITFIELDS, 108, &masks, rows);
+        assert_eq!(
+            Texture::parse_bmp(&data).unwrap().data,
+            [0xaa, 0xbb, 0xcc, 0xdd]
+        );
+
+        // the masks after a header of 40 bytes have no alpha
+        let data = bmp((1, 1), 32, BI_BITFIELDS, 40, &masks[..3], rows);
+        assert_eq!(
+            Texture::parse_bmp(&data).unwrap().data,
+            [0xaa, 0xbb, 0xcc, 255]
+        );
+
+        // unless the compression says so
+        let data = bmp((1, 1), 32, BI_ALPHABITFIELDS, 40, &masks, rows);
+        assert_eq!(
+            Texture::parse_bmp(&data).unwrap().data,
+            [0xaa, 0xbb, 0xcc, 0xdd]
+        );
+
+        // masks of less than 8 bits are scaled, 10 bits ones are reduced
+        let value = (0b11111 << 27) | (0b100000 << 21) | (0b1111111111 << 11) | 0b1;
+        let masks = [0xf800_0000, 0x07e0_0000, 0x001f_f800, 0x0000_0001];
+        let data = bmp(
+            (1, 1),
+            32,
+            BI_BITFIELDS,
+            56,
+            &masks,
+            &[&u32::to_le_bytes(value)],
+        );
+        assert_eq!(
+            Texture::parse_bmp(&data).unwrap().data,
+            [255, 129, 255, 255]
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let row: &[u8] = &[1, 2, 3, 0];
+        let data = bmp((1, 2), 24, BI_RGB, 40, &[], &[row, row]);
+        assert!(Texture::parse_bmp(&data).is_ok());
+        for len in [0, 2, FILE_HEADER_SIZE + 20, data.len() - 1] {
+            assert!(Texture::parse_bmp(&data[..len]).is_err(), "{len} bytes");
+        }
+        // masks announced by the compression but missing
+        let data = bmp((1, 1), 32, BI_BITFIELDS, 40, &[], &[]);
+        assert!(Texture::parse_bmp(&data).is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let row: &[u8] = &[0; 4];
+        let invalid = [
+            bmp((0, 1), 24, BI_RGB, 40, &[], &[row]),
+            bmp((-1, 1), 24, BI_RGB, 40, &[], &[row]),
+            bmp((1, 0), 24, BI_RGB, 40, &[], &[row]),
+            // a palette
+            bmp((1, 1), 8, BI_RGB, 40, &[], &[row]),
+            // run length encoded
+            bmp((1, 1), 24, 1, 40, &[], &[row]),
+            // the os/2 header
+            bmp((1, 1), 24, BI_RGB, 12, &[], &[row]),
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            assert!(Texture::parse_bmp(data).is_err(), "image {i}");
+        }
+        // the height of a huge image is not trusted
+        let mut data = bmp((1, 1), 24, BI_RGB, 40, &[], &[row]);
+        data[22..26].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(Texture::parse_bmp(&data).is_err());
+    }
+}
@@ -0,0 +1,520 @@
+use super::{
+    error::Warnings, material::Material, object::ParseOptions, Group, Object, Submesh, Topology,
+    Vertexxx,
+};
+use crate::math::Vec3;
+use std::{
+    env,
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 8] = b"SCOPMESH";
+// to bump whenever the layout of the file or the result of the parsing changes
+const VERSION: u32 = 5;
+
+// FNV-1a, unlike the std hasher its result is the same from one run to the next
+pub struct ContentHasher(u64);
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for ContentHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+// hash of the file at `path`, of where it is, of the files it references and of the options
+// it is parsed with, the cache of the file is invalidated when any of them changes. The same
+// file in two directories references other files, it has a cache in each of them
+pub fn hash_source(
+    path: &Path,
+    dependencies: &[PathBuf],
+    options: &ParseOptions,
+) -> io::Result<u64> {
+    let mut hasher = ContentHasher::default();
+    hash_content(&mut hasher, File::open(path)?)?;
+    hash_path(&mut hasher, &fs::canonicalize(path)?);
+    for dependency in dependencies {
+        hash_path(&mut hasher, dependency);
+        // a missing file is reported by the parsing
+        match File::open(dependency) {
+            Ok(file) => hash_content(&mut hasher, file)?,
+            Err(_) => hasher.write_u8(0),
+        }
+    }
+    hasher.write_u8(options.strict as u8);
+    hasher.write_u32(options.crease_angle.map_or(u32::MAX, f32::to_bits));
+
+    Ok(hasher.finish())
+}
+
+fn hash_content(hasher: &mut ContentHasher, mut reader: impl Read) -> io::Result<()> {
+    let mut buffer = vec![0; 1 << 16];
+    let mut length = 0;
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                hasher.write(&buffer[..read]);
+                length += read as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    // the length keeps the end of a file from being taken for the start of the next one
+    hasher.write_u64(length);
+    Ok(())
+}
+
+fn hash_path(hasher: &mut ContentHasher, path: &Path) {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    hasher.write(bytes);
+    hasher.write_u64(bytes.len() as u64);
+}
+
+// where the cache of the file with this hash is stored, in $XDG_CACHE_HOME/scop or
+// ~/.cache/scop
+pub fn cache_path(hash: u64) -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(dir.join("scop").join(format!("{hash:016x}.mesh")))
+}
+
+impl Object {
+    // the materials are only saved by name, `MeshLoader::load_materials` still has to be called
+    // the warnings of the parsing are saved after the object
+    pub fn write_cache(&self, path: &Path, hash: u64, warnings: &Warnings) -> io::Result<()> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        put_u32(&mut out, VERSION);
+        out.extend_from_slice(&hash.to_le_bytes());
+
+        out.push(match self.topology {
+            Topology::Triangles => 0,
+            Topology::Points => 1,
+        });
+        put_u32(&mut out, self.vertex.len() as u32);
+        for vertex in self.vertex.iter() {
+            for value in vertex
+                .position
+                .iter()
+                .chain(vertex.normal.iter())
+                .chain(vertex.color.iter())
+                .chain(vertex.texture.iter())
+            {
+                put_f32(&mut out, *value);
+            }
+        }
+
+        // most meshes fit in 16 bits indices, which halves their size
+        let short = self.vertex.len() <= u16::MAX as usize + 1;
+        out.push(if short { 2 } else { 4 });
+        put_u32(&mut out, self.indice.len() as u32);
+        for &index in self.indice.iter() {
+            if short {
+                out.extend_from_slice(&(index as u16).to_le_bytes());
+            } else {
+                put_u32(&mut out, index);
+            }
+        }
+
+        put_vec3(&mut out, &self.size);
+        put_vec3(&mut out, &self.center);
+
+        put_u32(&mut out, self.mtllibs.len() as u32);
+        for mtllib in self.mtllibs.iter() {
+            put_str(&mut out, mtllib);
+        }
+        put_u32(&mut out, self.materials.len() as u32);
+        for material in self.materials.iter() {
+            put_str(&mut out, &material.name);
+        }
+        put_u32(&mut out, self.submeshes.len() as u32);
+        for submesh in self.submeshes.iter() {
+            put_u32(&mut out, submesh.material.map_or(u32::MAX, |m| m as u32));
+            put_u32(&mut out, submesh.group as u32);
+            put_u32(&mut out, submesh.start);
+            put_u32(&mut out, submesh.count);
+        }
+        put_u32(&mut out, self.groups.len() as u32);
+        for group in self.groups.iter() {
+            put_str(&mut out, &group.name);
+            put_vec3(&mut out, &group.min);
+            put_vec3(&mut out, &group.max);
+        }
+
+        put_u32(&mut out, warnings.count as u32);
+        put_u32(&mut out, warnings.first.len() as u32);
+        for warning in warnings.first.iter() {
+            put_str(&mut out, warning);
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // written aside then renamed, so that another instance never reads half a file
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, path).inspect_err(|_| {
+            fs::remove_file(&tmp).ok();
+        })
+    }
+
+    // fails when the file was written for another source or by another version of scop
+    pub fn read_cache(path: &Path, hash: u64) -> io::Result<(Self, Warnings)> {
+        let data = fs::read(path)?;
+        let mut input = Input { data: &data };
+
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a mesh cache"));
+        }
+        if input.u32()? != VERSION {
+            return Err(invalid("written by another version"));
+        }
+        if input.u64()? != hash {
+            return Err(invalid("written for another file"));
+        }
+
+        let topology = match input.take(1)?[0] {
+            0 => Topology::Triangles,
+            1 => Topology::Points,
+            _ => return Err(invalid("bad topology")),
+        };
+        let vertex_count = input.u32()? as usize;
+        let mut vertex = Vec::with_capacity(vertex_count.min(input.data.len() / 44));
+        for _ in 0..vertex_count {
+            vertex.push(Vertexxx {
+                position: [input.f32()?, input.f32()?, input.f32()?],
+                normal: [input.f32()?, input.f32()?, input.f32()?],
+                color: [input.f32()?, input.f32()?, input.f32()?],
+                texture: [input.f32()?, input.f32()?],
+            });
+        }
+
+        let width = input.take(1)?[0];
+        let index_count = input.u32()? as usize;
+        let mut indice = Vec::with_capacity(index_count.min(input.data.len() / 2));
+        for _ in 0..index_count {
+            let index = match width {
+                2 => u16::from_le_bytes(input.array()?) as u32,
+                4 => input.u32()?,
+                _ => return Err(invalid("bad index width")),
+            };
+            if index as usize >= vertex_count {
+                return Err(invalid("index out of range"));
+            }
+            indice.push(index);
+        }
+
+        let size = input.vec3()?;
+        let center = input.vec3()?;
+
+        let mtllibs = (0..input.u32()?)
+            .map(|_| input.string())
+            .collect::<io::Result<_>>()?;
+        let materials: Vec<Material> = (0..input.u32()?)
+            .map(|_| {
+                Ok(Material {
+                    name: input.string()?,
+                    ..Default::default()
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let submeshes: Vec<Submesh> = (0..input.u32()?)
+            .map(|_| {
+                Ok(Submesh {
+                    material: match input.u32()? {
+                        u32::MAX => None,
+                        m => Some(m as usize),
+                    },
+                    group: input.u32()? as usize,
+                    start: input.u32()?,
+                    count: input.u32()?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let groups: Vec<Group> = (0..input.u32()?)
+            .map(|_| {
+                Ok(Group {
+                    name: input.string()?,
+                    min: input.vec3()?,
+                    max: input.vec3()?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let warnings = Warnings {
+            count: input.u32()? as usize,
+            first: (0..input.u32()?)
+                .map(|_| input.string())
+                .collect::<io::Result<_>>()?,
+        };
+
+        // the submeshes of point clouds are ranges of vertices
+        let drawn = match topology {
+            Topology::Triangles => indice.len(),
+            Topology::Points => vertex.len(),
+        };
+        for submesh in submeshes.iter() {
+            if submesh.start as u64 + submesh.count as u64 > drawn as u64
+                || submesh.group >= groups.len()
+                || submesh.material.is_some_and(|m| m >= materials.len())
+            {
+                return Err(invalid("submesh out of range"));
+            }
+        }
+
+        let object = Self {
+            vertex,
+            indice,
+            size,
+            center,
+            mtllibs,
+            materials,
+            submeshes,
+            groups,
+            topology,
+        };
+        Ok((object, warnings))
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f32(out: &mut Vec<u8>, value: f32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_vec3(out: &mut Vec<u8>, value: &Vec3) {
+    for value in value.to_array() {
+        put_f32(out, value);
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+// the part of the file not read yet
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn vec3(&mut self) -> io::Result<Vec3> {
+        Ok(Vec3 {
+            x: self.f32()?,
+            y: self.f32()?,
+            z: self.f32()?,
+        })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("invalid name"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // where the test `name` writes its cache, removed by `round_trip`
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("scop-test-{}-{name}.mesh", std::process::id()))
+    }
+
+    // the object read back from its cache, and the bytes of the cache
+    fn round_trip(name: &str, object: &Object) -> (Object, Vec<u8>) {
+        let path = temp_path(name);
+        object.write_cache(&path, 42, &Warnings::default()).unwrap();
+        let data = fs::read(&path).unwrap();
+        let read = Object::read_cache(&path, 42);
+        fs::remove_file(&path).unwrap();
+        let (read, warnings) = read.unwrap();
+        assert_eq!(warnings, Warnings::default());
+        (read, data)
+    }
+
+    // the width of the indices, stored after the vertices
+    fn index_width(object: &Object, data: &[u8]) -> u8 {
+        data[MAGIC.len() + 4 + 8 + 1 + 4 + object.vertex.len() * 44]
+    }
+
+    fn assert_same(a: &Object, b: &Object) {
+        assert_eq!(a.topology, b.topology);
+        assert_eq!(a.vertex, b.vertex);
+        assert_eq!(a.indice, b.indice);
+        assert_eq!(a.size.to_array(), b.size.to_array());
+        assert_eq!(a.center.to_array(), b.center.to_array());
+        assert_eq!(a.mtllibs, b.mtllibs);
+        let names = |o: &Object| {
+            o.materials
+                .iter()
+                .map(|m| m.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(a), names(b));
+        let submeshes = |o: &Object| {
+            o.submeshes
+                .iter()
+                .map(|s| (s.material, s.group, s.start, s.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(submeshes(a), submeshes(b));
+        let groups = |o: &Object| {
+            o.groups
+                .iter()
+                .map(|g| (g.name.clone(), g.min.to_array(), g.max.to_array()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(groups(a), groups(b));
+    }
+
+    fn vertex(i: usize) -> Vertexxx {
+        let i = i as f32;
+        Vertexxx {
+            position: [i, -i, i * 0.5],
+            normal: [0.0, 0.0, 1.0],
+            color: [i / 70000.0, 0.5, 1.0],
+            texture: [i, 1.0 - i],
+        }
+    }
+
+    #[test]
+    fn groups_and_materials() {
+        let obj = "mtllib a.mtl b.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0.5 0.5\n\
+                   vn 0 0 1\nf 1 2 3\ng left\nusemtl red\nf 1/1 3/1 4/1\ng right\n\
+                   usemtl blue\nf 1//1 2//1 3//1\nusemtl red\nf 1 3 4\n";
+        let (object, _) =
+            Object::parse_reader(obj.as_bytes(), &ParseOptions::default(), |_| {}).unwrap();
+        assert!(object.groups.len() > 1 && object.materials.len() == 2);
+        assert!(object.submeshes.iter().any(|s| s.material.is_none()));
+
+        let (read, data) = round_trip("groups", &object);
+        assert_eq!(index_width(&object, &data), 2);
+        assert_same(&object, &read);
+    }
+
+    #[test]
+    fn long_indices() {
+        // more vertices than 16 bits indices address
+        let mut object = Object::from_points((0..70000).map(vertex).collect());
+        object.topology = Topology::Triangles;
+        object.indice = (0..69999).chain([0, 1]).collect();
+        object.submeshes[0].count = object.indice.len() as u32;
+
+        let (read, data) = round_trip("long", &object);
+        assert_eq!(index_width(&object, &data), 4);
+        assert_same(&object, &read);
+    }
+
+    #[test]
+    fn points() {
+        let object = Object::from_points((0..100).map(vertex).collect());
+        let (read, _) = round_trip("points", &object);
+        assert_eq!(read.topology, Topology::Points);
+        assert_same(&object, &read);
+    }
+
+    #[test]
+    fn warnings() {
+        // more warnings than the ones kept
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".to_string() + &"x 1\n".repeat(30);
+        let (object, warnings) =
+            Object::parse_reader(obj.as_bytes(), &ParseOptions::default(), |_| {}).unwrap();
+        let warnings = Warnings::new(&warnings);
+        assert_eq!(warnings.count, 30);
+        assert_eq!(warnings.first.len(), 20);
+        assert_eq!(warnings.first[0], "5:1: unknown statement: 'x'");
+
+        let path = temp_path("warnings");
+        object.write_cache(&path, 42, &warnings).unwrap();
+        let read = Object::read_cache(&path, 42);
+        fs::remove_file(&path).unwrap();
+        let (read, read_warnings) = read.unwrap();
+        assert_same(&object, &read);
+        assert_eq!(read_warnings, warnings);
+    }
+
+    #[test]
+    fn rejected() {
+        let object = Object::from_points((0..3).map(vertex).collect());
+        let path = temp_path("rejected");
+        object.write_cache(&path, 42, &Warnings::default()).unwrap();
+        let data = fs::read(&path).unwrap();
+
+        // another source
+        assert!(Object::read_cache(&path, 43).is_err());
+        assert!(Object::read_cache(&path, 42).is_ok());
+
+        // another version, another kind of file, or truncated
+        let mut version = data.clone();
+        version[MAGIC.len()..][..4].copy_from_slice(&(VERSION - 1).to_le_bytes());
+        let mut magic = data.clone();
+        magic[0] = b'X';
+        let mut out_of_range = data.clone();
+        // the count of the submesh, the last field before the group and the warnings
+        let group_size = 4 + "default".len() + 24;
+        let count = data.len() - 8 - group_size - 4 - 4;
+        out_of_range[count..][..4].copy_from_slice(&4u32.to_le_bytes());
+        let invalid = [
+            version,
+            magic,
+            out_of_range,
+            data[..data.len() - 1].to_vec(),
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            fs::write(&path, data).unwrap();
+            assert!(Object::read_cache(&path, 42).is_err(), "cache {i}");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
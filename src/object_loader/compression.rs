@@ -0,0 +1,81 @@
+use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // the compression of a file, from its magic number. Its .gz or .zst extension is not
+    // trusted, it only hides the extension of the decompressed file
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    // the decompressed content of `reader`
+    pub fn decoder<'a>(self, reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(StreamingDecoder::new(reader).map_err(io::Error::other)?),
+        })
+    }
+}
+
+// the path of the file once decompressed, "teapot.obj.gz" becomes "teapot.obj", so that its
+// format can be told from its extension
+pub fn decompressed_path(path: &Path) -> PathBuf {
+    match extension(path).as_deref() {
+        Some("gz" | "zst") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+// the file at `path` opened for reading, decompressed when it is compressed
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let (compression, file) = open_raw(path)?;
+    match compression {
+        Some(compression) => compression.decoder(file),
+        None => Ok(Box::new(file)),
+    }
+}
+
+// the whole content of the file at `path`, decompressed when it is compressed
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// the file at `path` with its compression, which is found without consuming the file
+pub fn open_raw(path: &Path) -> io::Result<(Option<Compression>, File)> {
+    let mut file = File::open(path)?;
+    let mut header = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut file)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+    let compression = Compression::detect(&header);
+    file.rewind()?;
+    Ok((compression, file))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
@@ -0,0 +1,119 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+// the number of warnings printed for a file, the next ones are only counted
+const MAX_WARNINGS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjErrorKind {
+    InvalidNumber,
+    IndexOutOfRange,
+    UnknownStatement,
+    UnsupportedStatement,
+    MalformedFace,
+    // wrong number of values, with the expected format
+    MalformedStatement(&'static str),
+    // the file could not be read, the token is the reason
+    Io,
+}
+
+// in lenient mode the errors are reported as warnings, and the faulty statement is skipped
+pub type ParseWarning = ObjError;
+
+// the warnings of a parsed file as they are printed, they are kept in its cache to be
+// printed again when it is read from there
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Warnings {
+    // the first ones, without the file
+    pub first: Vec<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjError {
+    pub kind: ObjErrorKind,
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+}
+
+impl ObjError {
+    pub fn new(kind: ObjErrorKind, line: usize, column: usize, token: &str) -> Self {
+        Self {
+            kind,
+            file: None,
+            line,
+            column,
+            token: token.to_string(),
+        }
+    }
+
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber => write!(f, "invalid number"),
+            Self::IndexOutOfRange => write!(f, "index out of range"),
+            Self::UnknownStatement => write!(f, "unknown statement"),
+            Self::UnsupportedStatement => write!(f, "unsupported statement"),
+            Self::MalformedFace => write!(
+                f,
+                "malformed face, expected (a, b, c [, d...]) with v, v/vt, v//vn or v/vt/vn elements"
+            ),
+            Self::MalformedStatement(expected) => {
+                write!(f, "malformed statement, expected {expected} format")
+            }
+            Self::Io => write!(f, "read error"),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        write!(
+            f,
+            "{}:{}: {}: '{}'",
+            self.line, self.column, self.kind, self.token
+        )
+    }
+}
+
+impl Error for ObjError {}
+
+impl Warnings {
+    pub fn new(warnings: &[ParseWarning]) -> Self {
+        Self {
+            first: warnings
+                .iter()
+                .take(MAX_WARNINGS)
+                .map(ParseWarning::to_string)
+                .collect(),
+            count: warnings.len(),
+        }
+    }
+
+    // the warnings of the file at `path`
+    pub fn print(&self, path: &Path) {
+        for warning in self.first.iter() {
+            println!("obj warning: {}:{warning}", path.display());
+        }
+        if self.count > self.first.len() {
+            println!(
+                "obj warning: {} more warnings",
+                self.count - self.first.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use super::{material::Material, Object, Topology, Vertexxx};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    hash::Hash,
+    io::{self, BufWriter, Write},
+    path::{self, Component, Path, PathBuf},
+};
+
+impl Object {
+    // writes the object to the obj file `path`, and its materials, when it has some, to an mtl
+    // file of the same name next to it
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let mtl_path = path.with_extension("mtl");
+        let mtllib = if self.materials.is_empty() {
+            None
+        } else {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let mut out = BufWriter::new(File::create(&mtl_path)?);
+            self.write_mtl(&mut out, dir)?;
+            out.flush()?;
+            mtl_path.file_name().and_then(|name| name.to_str())
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut out, mtllib)?;
+        out.flush()
+    }
+
+    // writes the object as it is rendered: triangulated, with its generated normals and
+    // texture coordinates. Positions, texture coordinates and normals shared by several
+    // vertices are only written once. `mtllib` is the name of the mtl file of the materials,
+    // written with `write_mtl`
+    pub fn write_obj(&self, out: &mut impl Write, mtllib: Option<&str>) -> io::Result<()> {
+        writeln!(out, "# exported by scop")?;
+        if let Some(mtllib) = mtllib {
+            writeln!(out, "mtllib {mtllib}")?;
+        }
+
+        // the colors are only written when there are some, as an extension of v
+        let has_colors = self.vertex.iter().any(|v| v.color != [1.0, 1.0, 1.0]);
+
+        // point clouds are written as positions alone
+        if self.topology == Topology::Points {
+            for vertex in self.vertex.iter() {
+                write_position(out, vertex, has_colors)?;
+            }
+            return Ok(());
+        }
+
+        // obj indices of the position, texture coordinates and normal of each vertex
+        let mut elements = vec![[0; 3]; self.vertex.len()];
+        let mut positions = HashMap::new();
+        let mut textures = HashMap::new();
+        let mut normals = HashMap::new();
+        for submesh in self.submeshes.iter() {
+            let range = submesh.start as usize..(submesh.start + submesh.count) as usize;
+            for &i in self.indice[range].iter() {
+                let vertex = &self.vertex[i as usize];
+                let element = &mut elements[i as usize];
+                if element[0] != 0 {
+                    continue;
+                }
+
+                let key = (
+                    vertex.position.map(f32::to_bits),
+                    vertex.color.map(f32::to_bits),
+                );
+                element[0] = element_index(&mut positions, key, || {
+                    write_position(out, vertex, has_colors)
+                })?;
+                element[1] =
+                    element_index(&mut textures, vertex.texture.map(f32::to_bits), || {
+                        // the v axis is flipped when parsing
+                        let [u, v] = vertex.texture;
+                        writeln!(out, "vt {u} {}", 1.0 - v)
+                    })?;
+                element[2] = element_index(&mut normals, vertex.normal.map(f32::to_bits), || {
+                    let [x, y, z] = vertex.normal;
+                    writeln!(out, "vn {x} {y} {z}")
+                })?;
+            }
+        }
+
+        let mut group = None;
+        let mut material = None;
+        for submesh in self.submeshes.iter() {
+            if group != Some(submesh.group) {
+                group = Some(submesh.group);
+                writeln!(out, "g {}", self.groups[submesh.group].name)?;
+            }
+            if let Some(m) = submesh.material.filter(|&m| material != Some(m)) {
+                material = Some(m);
+                writeln!(out, "usemtl {}", self.materials[m].name)?;
+            }
+
+            let range = submesh.start as usize..(submesh.start + submesh.count) as usize;
+            for triangle in self.indice[range].chunks_exact(3) {
+                write!(out, "f")?;
+                for &i in triangle {
+                    let [v, vt, vn] = elements[i as usize];
+                    write!(out, " {v}/{vt}/{vn}")?;
+                }
+                writeln!(out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the texture paths are rewritten relative to `dir`, the directory of the mtl file
+    pub fn write_mtl(&self, out: &mut impl Write, dir: &Path) -> io::Result<()> {
+        writeln!(out, "# exported by scop")?;
+        for material in self.materials.iter() {
+            material.write(out, dir)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_position(out: &mut impl Write, vertex: &Vertexxx, has_colors: bool) -> io::Result<()> {
+    let [x, y, z] = vertex.position;
+    if has_colors {
+        let [r, g, b] = vertex.color;
+        writeln!(out, "v {x} {y} {z} {r} {g} {b}")
+    } else {
+        writeln!(out, "v {x} {y} {z}")
+    }
+}
+
+// the obj index of `key`, its element is written the first time it is met
+fn element_index<K: Hash + Eq>(
+    indices: &mut HashMap<K, usize>,
+    key: K,
+    write: impl FnOnce() -> io::Result<()>,
+) -> io::Result<usize> {
+    if let Some(&index) = indices.get(&key) {
+        return Ok(index);
+    }
+    write()?;
+    let index = indices.len() + 1;
+    indices.insert(key, index);
+    Ok(index)
+}
+
+impl Material {
+    fn write(&self, out: &mut impl Write, dir: &Path) -> io::Result<()> {
+        let map = |map: &str| relative_path(&self.dir.join(map), dir);
+        let [r, g, b] = self.ambient;
+        writeln!(out, "\nnewmtl {}", self.name)?;
+        writeln!(out, "Ka {r} {g} {b}")?;
+        let [r, g, b] = self.diffuse;
+        writeln!(out, "Kd {r} {g} {b}")?;
+        let [r, g, b] = self.specular;
+        writeln!(out, "Ks {r} {g} {b}")?;
+        writeln!(out, "Ns {}", self.shininess)?;
+        writeln!(out, "d {}", self.dissolve)?;
+        writeln!(out, "illum {}", self.illum)?;
+        if let Some(map_kd) = &self.map_kd {
+            writeln!(out, "map_Kd {}", map(map_kd).display())?;
+        }
+        if let Some(map_bump) = &self.map_bump {
+            writeln!(out, "map_Bump {}", map(map_bump).display())?;
+        }
+        Ok(())
+    }
+}
+
+// `path` relative to `dir`, both starting from the current directory when they are relative.
+// A path on another root than `dir`, as another drive, stays absolute
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    let (Some(path_components), Some(dir)) = (absolute(path), absolute(dir)) else {
+        return path.to_path_buf();
+    };
+    let common = path_components
+        .iter()
+        .zip(dir.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return path_components.iter().collect();
+    }
+    let mut relative: PathBuf = dir[common..].iter().map(|_| "..").collect();
+    relative.extend(&path_components[common..]);
+    relative
+}
+
+// the components of `path` made absolute, without the . and .. that can be removed. The
+// symbolic links are not followed, the files do not have to exist
+fn absolute(path: &Path) -> Option<Vec<OsString>> {
+    let mut components = Vec::new();
+    let mut names = 0;
+    for component in path::absolute(path).ok()?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if names > 0 {
+                    components.pop();
+                    names -= 1;
+                }
+            }
+            Component::Normal(name) => {
+                components.push(name.to_owned());
+                names += 1;
+            }
+            Component::Prefix(_) | Component::RootDir => {
+                components.push(component.as_os_str().to_owned())
+            }
+        }
+    }
+    Some(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::object::ParseOptions;
+    use std::{env, fs};
+
+    const OBJ: &str = "mtllib model.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 0.25 0
+vt 0.25 0.75
+vt 0 0.5
+vn 0 0 1
+vn 0 -1 0
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g side
+usemtl textured
+f 1/1/2 2/2/2 5/4/2
+";
+    const MTL: &str = "newmtl red\nKd 1 0 0\nnewmtl textured\nKd 0.5 0.5 0.5\n\
+                       map_Kd textures/brick.png\n";
+
+    // the position, texture coordinates and normal of each corner of the triangles
+    fn corners(object: &Object) -> Vec<[f32; 8]> {
+        object
+            .indice
+            .iter()
+            .map(|&i| {
+                let v = &object.vertex[i as usize];
+                let [x, y, z] = v.position;
+                let [u, tv] = v.texture;
+                let [nx, ny, nz] = v.normal;
+                [x, y, z, u, tv, nx, ny, nz]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn relative_paths() {
+        let cases = [
+            ("/a/b/tex.png", "/a/b", "tex.png"),
+            ("/a/b/tex.png", "/a/c/d", "../../b/tex.png"),
+            ("/a/./b/../b/tex.png", "/a/c/..", "b/tex.png"),
+            ("/tex.png", "/a", "../tex.png"),
+        ];
+        for (path, dir, relative) in cases {
+            assert_eq!(
+                relative_path(Path::new(path), Path::new(dir)),
+                Path::new(relative),
+                "{path} from {dir}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let root = env::temp_dir().join(format!("scop-test-{}-export", std::process::id()));
+        let (input, output) = (root.join("in"), root.join("out"));
+        fs::create_dir_all(&input).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(input.join("model.mtl"), MTL).unwrap();
+
+        let parse = |obj: &[u8], dir: &Path| {
+            let (mut object, _) =
+                Object::parse_reader(obj, &ParseOptions::default(), |_| {}).unwrap();
+            object.load_materials(dir);
+            object
+        };
+        let object = parse(OBJ.as_bytes(), &input);
+        object.export(&output.join("model.obj")).unwrap();
+        let obj = fs::read_to_string(output.join("model.obj")).unwrap();
+        let mtl = fs::read_to_string(output.join("model.mtl")).unwrap();
+        let exported = parse(obj.as_bytes(), &output);
+        fs::remove_dir_all(&root).unwrap();
+
+        // the texture coordinates are written as they were read, not flipped
+        assert!(obj.lines().any(|line| line == "vt 0.25 0.75"));
+        // the texture is found from the new directory
+        let brick = Path::new("..")
+            .join("in")
+            .join("textures")
+            .join("brick.png");
+        assert!(mtl.contains(&format!("map_Kd {}\n", brick.display())));
+
+        assert_eq!(corners(&object), corners(&exported));
+        let groups = |o: &Object| {
+            o.submeshes
+                .iter()
+                .map(|s| (o.groups[s.group].name.clone(), s.material, s.start, s.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(groups(&object), groups(&exported));
+        // the textures are the same files
+        let materials = |o: &Object| {
+            o.materials
+                .iter()
+                .map(|m| {
+                    let texture = m.map_kd.as_ref().and_then(|map| absolute(&m.dir.join(map)));
+                    (m.name.clone(), m.diffuse, texture)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(materials(&object), materials(&exported));
+        assert!(materials(&object)[1].2.is_some());
+    }
+}
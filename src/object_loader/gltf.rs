@@ -0,0 +1,1028 @@
+use super::{
+    json::Json,
+    loader,
+    material::Material,
+    object::{ObjParser, ParseOptions},
+    Object,
+};
+use crate::math::{Mat4, Vec3};
+use std::{
+    borrow::Cow,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_SIZE: usize = 12;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+// the files of the external buffers and images of a gltf file, relative to `path`
+pub fn external_files(data: &[u8], path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let gltf = Gltf::load(data, path)?;
+    let buffers = gltf.json.get("buffers").elements();
+    let images = gltf.json.get("images").elements();
+    Ok(buffers
+        .iter()
+        .chain(images)
+        .filter_map(|resource| resource.get("uri").as_str())
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(|uri| gltf.dir.join(decode_uri(uri)))
+        .collect())
+}
+
+impl Object {
+    // loads a gltf 2.0 file, as json or as binary glb. The meshes of the default scene are
+    // flattened with the transforms of their nodes, each node being a group. External buffers
+    // are looked up relative to `path`. The materials are only named, `Material::parse_gltf`
+    // reads their definitions
+    pub fn parse_gltf(
+        data: &[u8],
+        path: &Path,
+        options: &ParseOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let gltf = Gltf::load(data, path)?;
+        let buffers = gltf.buffers()?;
+        let materials = material_names(&gltf.json);
+
+        let mut instances = Vec::new();
+        let nodes = gltf.json.get("nodes");
+        for root in gltf.scene_roots()? {
+            visit_node(nodes, root, Mat4::identity(), 0, &mut instances)?;
+        }
+
+        let mut parser = ObjParser::new(options);
+        let meshes = gltf.json.get("meshes");
+        for (node, mesh, matrix) in instances {
+            let name = nodes.at(node).get("name").as_str();
+            let name = name
+                .or(meshes.at(mesh).get("name").as_str())
+                .filter(|name| !name.is_empty())
+                .map_or_else(|| format!("mesh{mesh}"), str::to_string);
+            parser.start_group(&name);
+            for primitive in meshes.at(mesh).get("primitives").elements() {
+                let material = match primitive.get("material") {
+                    Json::Null => None,
+                    index => Some(
+                        index
+                            .as_usize()
+                            .and_then(|i| materials.get(i))
+                            .ok_or(format!("mesh {name}: invalid material"))?
+                            .as_str(),
+                    ),
+                };
+                parser.use_material(material);
+                gltf.push_primitive(primitive, &buffers, &matrix, &mut parser)
+                    .map_err(|e| format!("mesh {name}: {e}"))?;
+            }
+        }
+        if parser.triangles.is_empty() {
+            return Err("the gltf file has no triangles".into());
+        }
+
+        let (obj, _) = parser.finish();
+        Ok(obj)
+    }
+}
+
+impl Material {
+    // the materials of a gltf file with their base color factor and texture. External
+    // textures are only named in map_kd, to be loaded by `load_textures`
+    pub fn parse_gltf(data: &[u8], path: &Path) -> Result<Vec<Self>, Box<dyn Error>> {
+        let gltf = Gltf::load(data, path)?;
+        let names = material_names(&gltf.json);
+
+        let mut materials = Vec::new();
+        for (definition, name) in gltf.json.get("materials").elements().iter().zip(names) {
+            let pbr = definition.get("pbrMetallicRoughness");
+            let mut material = Material {
+                name,
+                ..Default::default()
+            };
+            if let [r, g, b, a] = numbers(pbr.get("baseColorFactor"))[..] {
+                material.diffuse = [r, g, b];
+                material.dissolve = a;
+            }
+
+            let texture = pbr.get("baseColorTexture").get("index");
+            if let Some(texture) = texture.as_usize() {
+                let image = gltf.json.get("textures").at(texture).get("source");
+                if let Err(e) = gltf.load_image(image, &mut material) {
+                    println!(
+                        "gltf warning: material {}: failed to load its texture: {e}",
+                        material.name
+                    );
+                }
+            }
+            materials.push(material);
+        }
+        Ok(materials)
+    }
+}
+
+// the names of the materials, made unique as they are looked up by name
+fn material_names(json: &Json) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (i, material) in json.get("materials").elements().iter().enumerate() {
+        let mut name = match material.get("name").as_str() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("material{i}"),
+        };
+        if names.contains(&name) {
+            name = format!("{name}.{i}");
+        }
+        names.push(name);
+    }
+    names
+}
+
+// adds the meshes of the node and of its children to `instances`, with the transform that
+// takes them to the scene
+fn visit_node(
+    nodes: &Json,
+    index: usize,
+    parent: Mat4,
+    depth: usize,
+    instances: &mut Vec<(usize, usize, Mat4)>,
+) -> Result<(), Box<dyn Error>> {
+    // a tree can't be deeper than its number of nodes
+    if depth > nodes.elements().len() {
+        return Err("the node hierarchy has a cycle".into());
+    }
+    let node = nodes.at(index);
+    if node.is_null() {
+        return Err(format!("invalid node {index}").into());
+    }
+
+    let matrix = parent * node_matrix(node)?;
+    if let Some(mesh) = node.get("mesh").as_usize() {
+        instances.push((index, mesh, matrix));
+    }
+    for child in node.get("children").elements() {
+        let child = child.as_usize().ok_or("invalid child node")?;
+        visit_node(nodes, child, matrix, depth + 1, instances)?;
+    }
+    Ok(())
+}
+
+// the transform of a node relative to its parent, the matrices of this module are indexed
+// by row then column
+fn node_matrix(node: &Json) -> Result<Mat4, Box<dyn Error>> {
+    if !node.get("matrix").is_null() {
+        let values = numbers(node.get("matrix"));
+        if values.len() != 16 {
+            return Err("node matrix without 16 values".into());
+        }
+        // gltf matrices are stored column by column
+        let mut matrix = Mat4::default();
+        for (i, value) in values.into_iter().enumerate() {
+            matrix[i % 4][i / 4] = value;
+        }
+        return Ok(matrix);
+    }
+
+    let mut matrix = Mat4::identity();
+    if let [x, y, z] = numbers(node.get("translation"))[..] {
+        matrix *= Mat4::translation(x, y, z);
+    }
+    if let [x, y, z, w] = numbers(node.get("rotation"))[..] {
+        matrix *= Mat4::rotate_quaternion([x, y, z, w]);
+    }
+    if let [x, y, z] = numbers(node.get("scale"))[..] {
+        matrix *= Mat4::scale(x, y, z);
+    }
+    Ok(matrix)
+}
+
+fn numbers(array: &Json) -> Vec<f32> {
+    array
+        .elements()
+        .iter()
+        .filter_map(|value| value.as_f64().map(|v| v as f32))
+        .collect()
+}
+
+// the json document and the binary chunk of a glb
+struct Gltf<'a> {
+    json: Json,
+    bin: Option<&'a [u8]>,
+    dir: &'a Path,
+}
+
+impl<'a> Gltf<'a> {
+    fn load(data: &'a [u8], path: &'a Path) -> Result<Self, Box<dyn Error>> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let Glb { json, bin } = if data.starts_with(GLB_MAGIC) {
+            parse_glb(data)?
+        } else {
+            Glb {
+                json: data,
+                bin: None,
+            }
+        };
+        let json = std::str::from_utf8(json)?;
+        let json = Json::parse(json.trim_start_matches('\u{feff}'))?;
+
+        let version = json.get("asset").get("version").as_str();
+        if !version.is_some_and(|v| v.starts_with("2.")) {
+            return Err("only gltf 2.0 files are supported".into());
+        }
+        Ok(Self { json, bin, dir })
+    }
+
+    // the roots of the default scene, or all the roots when the file has no scene
+    fn scene_roots(&self) -> Result<Vec<usize>, Box<dyn Error>> {
+        let scenes = self.json.get("scenes");
+        if scenes.elements().is_empty() {
+            let nodes = self.json.get("nodes").elements();
+            let mut is_child = vec![false; nodes.len()];
+            for node in nodes {
+                for child in node.get("children").elements() {
+                    if let Some(flag) = child.as_usize().and_then(|c| is_child.get_mut(c)) {
+                        *flag = true;
+                    }
+                }
+            }
+            return Ok((0..nodes.len()).filter(|&i| !is_child[i]).collect());
+        }
+
+        let scene = self.json.get("scene").as_usize().unwrap_or(0);
+        scenes
+            .at(scene)
+            .get("nodes")
+            .elements()
+            .iter()
+            .map(|node| node.as_usize().ok_or_else(|| "invalid scene node".into()))
+            .collect()
+    }
+
+    fn buffers(&self) -> Result<Vec<Cow<'a, [u8]>>, Box<dyn Error>> {
+        (0..self.json.get("buffers").elements().len())
+            .map(|i| self.buffer(i))
+            .collect()
+    }
+
+    // the content of a buffer, from the glb chunk, a data uri or a file next to the gltf
+    fn buffer(&self, index: usize) -> Result<Cow<'a, [u8]>, Box<dyn Error>> {
+        let buffer = self.json.get("buffers").at(index);
+        let data: Cow<[u8]> = match buffer.get("uri").as_str() {
+            Some(uri) => Cow::Owned(self.read_uri(uri)?),
+            None => Cow::Borrowed(
+                self.bin
+                    .filter(|_| index == 0)
+                    .ok_or(format!("buffer {index} has no data"))?,
+            ),
+        };
+        let length = buffer.get("byteLength").as_usize();
+        match length {
+            Some(length) if length <= data.len() => Ok(data),
+            _ => Err(format!("buffer {index} is shorter than its byteLength").into()),
+        }
+    }
+
+    // the content of a data uri or of a file
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (header, content) = data.split_once(',').ok_or("invalid data uri")?;
+            if !header.ends_with(";base64") {
+                return Err("only base64 data uris are supported".into());
+            }
+            return decode_base64(content);
+        }
+        let path = self.dir.join(decode_uri(uri));
+        Ok(fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?)
+    }
+
+    fn buffer_view<'b>(
+        &self,
+        index: usize,
+        buffers: &'b [Cow<[u8]>],
+    ) -> Result<BufferView<'b>, Box<dyn Error>> {
+        let view = self.json.get("bufferViews").at(index);
+        let buffer = view
+            .get("buffer")
+            .as_usize()
+            .and_then(|i| buffers.get(i))
+            .ok_or(format!("buffer view {index} has an invalid buffer"))?;
+        let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+        let length = view
+            .get("byteLength")
+            .as_usize()
+            .ok_or(format!("buffer view {index} has no byteLength"))?;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or(format!("buffer view {index} is out of its buffer"))?;
+        Ok(BufferView {
+            data,
+            stride: view.get("byteStride").as_usize(),
+        })
+    }
+
+    fn accessor<'b>(
+        &self,
+        index: &Json,
+        buffers: &'b [Cow<[u8]>],
+    ) -> Result<Accessor<'b>, Box<dyn Error>> {
+        let accessor = index
+            .as_usize()
+            .map(|i| self.json.get("accessors").at(i))
+            .filter(|accessor| !accessor.is_null())
+            .ok_or("invalid accessor")?;
+        if !accessor.get("sparse").is_null() {
+            return Err("sparse accessors are not supported".into());
+        }
+
+        let component = match accessor.get("componentType").as_usize() {
+            Some(5120) => Component::I8,
+            Some(5121) => Component::U8,
+            Some(5122) => Component::I16,
+            Some(5123) => Component::U16,
+            Some(5125) => Component::U32,
+            Some(5126) => Component::F32,
+            _ => return Err("accessor with an invalid componentType".into()),
+        };
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err("accessor with an unsupported type".into()),
+        };
+        let count = accessor
+            .get("count")
+            .as_usize()
+            .ok_or("accessor without count")?;
+        let mut result = Accessor {
+            data: None,
+            stride: 0,
+            count,
+            component,
+            components,
+            normalized: accessor.get("normalized").as_bool().unwrap_or(false),
+        };
+
+        // without a buffer view, all the values are zeros
+        let Some(view) = accessor.get("bufferView").as_usize() else {
+            return Ok(result);
+        };
+        let BufferView { data, stride } = self.buffer_view(view, buffers)?;
+        let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+        let element_size = components * component.size();
+        result.stride = stride.unwrap_or(element_size);
+        if count > 0 {
+            let end = result
+                .stride
+                .checked_mul(count - 1)
+                .and_then(|last| last.checked_add(offset))
+                .and_then(|last| last.checked_add(element_size));
+            if result.stride < element_size || end.is_none_or(|end| end > data.len()) {
+                return Err("accessor out of its buffer view".into());
+            }
+        }
+        result.data = Some(&data[offset.min(data.len())..]);
+        Ok(result)
+    }
+
+    fn push_primitive(
+        &self,
+        primitive: &Json,
+        buffers: &[Cow<[u8]>],
+        matrix: &Mat4,
+        parser: &mut ObjParser,
+    ) -> Result<(), Box<dyn Error>> {
+        let mode = primitive.get("mode").as_usize().unwrap_or(MODE_TRIANGLES);
+        if ![MODE_TRIANGLES, MODE_TRIANGLE_STRIP, MODE_TRIANGLE_FAN].contains(&mode) {
+            println!("gltf warning: points and lines are ignored");
+            return Ok(());
+        }
+
+        let attributes = primitive.get("attributes");
+        let positions = self.accessor(attributes.get("POSITION"), buffers)?;
+        let normals = match attributes.get("NORMAL") {
+            Json::Null => None,
+            index => Some(self.accessor(index, buffers)?),
+        };
+        let textures = match attributes.get("TEXCOORD_0") {
+            Json::Null => None,
+            index => Some(self.accessor(index, buffers)?),
+        };
+        let colors = match attributes.get("COLOR_0") {
+            Json::Null => None,
+            index => Some(self.accessor(index, buffers)?),
+        };
+        let count = positions.count;
+        for attribute in [&normals, &textures, &colors].into_iter().flatten() {
+            if attribute.count != count {
+                return Err("attributes with different counts".into());
+            }
+        }
+
+        // normals follow the inverse transpose of the transform, a mirroring one also turns
+        // the faces around
+        let (normal_matrix, determinant) = normal_matrix(matrix);
+        let lists = &mut parser.lists;
+        let position_base = lists.v.len();
+        let texture_base = lists.vt.len();
+        let normal_base = lists.vn.len();
+        for i in 0..count {
+            let position = Vec3::from(&positions.vec3(i));
+            let color = colors.as_ref().map_or([1.0; 3], |colors| colors.vec3(i));
+            lists
+                .v
+                .push([matrix.transform_point(&position).to_array(), color]);
+            if let Some(normals) = &normals {
+                let normal = Vec3::from(&normals.vec3(i)) * normal_matrix * determinant.signum();
+                let normal = if normal.length() > 0.0 {
+                    normal.normalize().to_array()
+                } else {
+                    [0.0; 3]
+                };
+                lists.vn.push(normal);
+            }
+            if let Some(textures) = &textures {
+                // gltf texture coordinates start at the top left, as the ones of scop
+                lists.vt.push([textures.get(i, 0), textures.get(i, 1)]);
+            }
+        }
+
+        let indices: Vec<usize> = match primitive.get("indices") {
+            Json::Null => (0..count).collect(),
+            index => {
+                let indices = self.accessor(index, buffers)?;
+                if indices.components != 1 {
+                    return Err("indices that are not scalars".into());
+                }
+                (0..indices.count)
+                    .map(|i| indices.value(i, 0) as usize)
+                    .collect()
+            }
+        };
+        if let Some(index) = indices.iter().find(|&&index| index >= count) {
+            return Err(format!("index {index} out of range").into());
+        }
+
+        let triangle_count = match mode {
+            MODE_TRIANGLES => indices.len() / 3,
+            _ => indices.len().saturating_sub(2),
+        };
+        for t in 0..triangle_count {
+            let mut triangle = match mode {
+                MODE_TRIANGLES => [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]],
+                // every other triangle of a strip is turned around to keep the same winding
+                MODE_TRIANGLE_STRIP if t % 2 == 1 => [indices[t + 1], indices[t], indices[t + 2]],
+                MODE_TRIANGLE_STRIP => [indices[t], indices[t + 1], indices[t + 2]],
+                _ => [indices[0], indices[t + 1], indices[t + 2]],
+            };
+            // the degenerate triangles that join strips
+            if triangle[0] == triangle[1]
+                || triangle[1] == triangle[2]
+                || triangle[0] == triangle[2]
+            {
+                continue;
+            }
+            if determinant < 0.0 {
+                triangle.swap(1, 2);
+            }
+
+            parser.polygon.clear();
+            for i in triangle {
+                let vertex = parser.lists.face_vertex(
+                    position_base + i,
+                    textures.is_some().then_some(texture_base + i),
+                    normals.is_some().then_some(normal_base + i),
+                );
+                parser.polygon.push(vertex);
+            }
+            parser.push_polygon();
+        }
+        Ok(())
+    }
+
+    // decodes the image of a texture, or names it in map_kd when it is an external file
+    fn load_image(&self, image: &Json, material: &mut Material) -> Result<(), Box<dyn Error>> {
+        let image = image
+            .as_usize()
+            .map(|i| self.json.get("images").at(i))
+            .filter(|image| !image.is_null())
+            .ok_or("invalid image")?;
+
+        let data = match (
+            image.get("uri").as_str(),
+            image.get("bufferView").as_usize(),
+        ) {
+            (Some(uri), _) if !uri.starts_with("data:") => {
+                material.map_kd = Some(decode_uri(uri));
+                return Ok(());
+            }
+            (Some(uri), _) => self.read_uri(uri)?,
+            (None, Some(view)) => {
+                let buffer = self
+                    .json
+                    .get("bufferViews")
+                    .at(view)
+                    .get("buffer")
+                    .as_usize()
+                    .ok_or("invalid buffer view")?;
+                // the buffers before it are only there to keep the indices right
+                let mut buffers = vec![Cow::Borrowed(&[][..]); buffer];
+                buffers.push(self.buffer(buffer)?);
+                self.buffer_view(view, &buffers)?.data.to_vec()
+            }
+            (None, None) => return Err("image without data".into()),
+        };
+        material.diffuse_map = Some(loader::decode_image(None, &data)?);
+        Ok(())
+    }
+}
+
+fn parse_glb(data: &[u8]) -> Result<Glb<'_>, Box<dyn Error>> {
+    let u32_at = |offset: usize| {
+        data.get(offset..offset.saturating_add(4))
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or("glb file truncated")
+    };
+    if u32_at(4)? != 2 {
+        return Err("only glb files of version 2 are supported".into());
+    }
+    let length = u32_at(8)? as usize;
+    let data = data.get(..length).ok_or("glb file truncated")?;
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = GLB_HEADER_SIZE;
+    while offset < data.len() {
+        let chunk_length = u32_at(offset)? as usize;
+        let chunk_type = u32_at(offset + 4)?;
+        let chunk = (offset + 8)
+            .checked_add(chunk_length)
+            .and_then(|end| data.get(offset + 8..end))
+            .ok_or("glb chunk truncated")?;
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            // unknown chunks are skipped
+            _ => {}
+        }
+        // chunks are aligned on 4 bytes
+        offset += 8 + chunk_length.next_multiple_of(4);
+    }
+    Ok(Glb {
+        json: json.ok_or("glb file without json chunk")?,
+        bin,
+    })
+}
+
+// the chunks of a glb file
+struct Glb<'a> {
+    json: &'a [u8],
+    bin: Option<&'a [u8]>,
+}
+
+// the bytes of a buffer view and the stride of its elements
+struct BufferView<'a> {
+    data: &'a [u8],
+    stride: Option<usize>,
+}
+
+// the inverse transpose of the upper 3x3 of `matrix` up to a factor, as its cofactor matrix,
+// and its determinant
+fn normal_matrix(matrix: &Mat4) -> (Mat4, f32) {
+    let m = |row: usize, col: usize| matrix[row % 3][col % 3];
+    let mut cofactors = Mat4::identity();
+    for row in 0..3 {
+        for col in 0..3 {
+            cofactors[row][col] = m(row + 1, col + 1) * m(row + 2, col + 2)
+                - m(row + 1, col + 2) * m(row + 2, col + 1);
+        }
+    }
+    let determinant = (0..3).map(|col| m(0, col) * cofactors[0][col]).sum();
+    (cofactors, determinant)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Component {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32,
+}
+
+impl Component {
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::U32 | Self::F32 => 4,
+        }
+    }
+}
+
+// typed view of the elements of a buffer view
+struct Accessor<'a> {
+    data: Option<&'a [u8]>,
+    stride: usize,
+    count: usize,
+    component: Component,
+    components: usize,
+    normalized: bool,
+}
+
+impl Accessor<'_> {
+    // the component `c` of the element `i`, 0 when the element has less components. It is
+    // exact for the integer types, which indices use
+    fn value(&self, i: usize, c: usize) -> f64 {
+        let Some(data) = self.data.filter(|_| c < self.components) else {
+            return 0.0;
+        };
+        let offset = i * self.stride + c * self.component.size();
+        let bytes = &data[offset..offset + self.component.size()];
+        match self.component {
+            Component::I8 => bytes[0] as i8 as f64,
+            Component::U8 => bytes[0] as f64,
+            Component::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Component::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Component::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Component::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        }
+    }
+
+    // normalized integers are mapped to [0, 1], or [-1, 1] when they are signed
+    fn get(&self, i: usize, c: usize) -> f32 {
+        let value = self.value(i, c) as f32;
+        if !self.normalized {
+            return value;
+        }
+        match self.component {
+            Component::I8 => (value / i8::MAX as f32).max(-1.0),
+            Component::U8 => value / u8::MAX as f32,
+            Component::I16 => (value / i16::MAX as f32).max(-1.0),
+            Component::U16 => value / u16::MAX as f32,
+            _ => value,
+        }
+    }
+
+    fn vec3(&self, i: usize) -> [f32; 3] {
+        [self.get(i, 0), self.get(i, 1), self.get(i, 2)]
+    }
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for byte in text.bytes().take_while(|&b| b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err("invalid base64 data".into()),
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(data)
+}
+
+// uris of files may have escaped characters, like %20 for spaces
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the corners of the triangles of a group: their positions, normals and texture
+    // coordinates
+    type Triangle = ([[f32; 3]; 3], [[f32; 3]; 3], [[f32; 2]; 3]);
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in data.chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - i * 8));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    text.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 63] as char);
+                } else {
+                    text.push('=');
+                }
+            }
+        }
+        text
+    }
+
+    // the positions, normals and texture coordinates of a triangle, then its u16 indices
+    fn triangle_buffer(positions: [[f32; 3]; 3], normal: [f32; 3]) -> Vec<u8> {
+        let (normals, uvs) = ([normal; 3], [[0.0, 0.0], [1.0, 0.0], [0.25, 0.75]]);
+        let floats = positions
+            .iter()
+            .flatten()
+            .chain(normals.iter().flatten())
+            .chain(uvs.iter().flatten());
+        let mut data: Vec<u8> = floats.flat_map(|f: &f32| f.to_le_bytes()).collect();
+        data.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        data
+    }
+
+    // a file whose nodes instance a single triangle, `buffer` is the json of its buffer
+    fn gltf(buffer: &str, nodes: &str, scene: &str) -> String {
+        format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": {scene} }}],
+  "nodes": {nodes},
+  "meshes": [{{
+    "name": "triangle",
+    "primitives": [{{
+      "attributes": {{ "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }},
+      "indices": 3,
+      "material": 0
+    }}]
+  }}],
+  "materials": [{{ "name": "red", "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0, 0, 1] }} }}],
+  "buffers": [{buffer}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteLength": 96 }},
+    {{ "buffer": 0, "byteOffset": 96, "byteLength": 6 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+    {{ "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" }},
+    {{ "bufferView": 0, "byteOffset": 72, "componentType": 5126, "count": 3, "type": "VEC2" }},
+    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+  ]
+}}"#
+        )
+    }
+
+    fn embedded(data: &[u8], nodes: &str, scene: &str) -> String {
+        let buffer = format!(
+            r#"{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}"#,
+            data.len(),
+            base64(data)
+        );
+        gltf(&buffer, nodes, scene)
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        for (kind, data, padding) in [(CHUNK_JSON, json.as_bytes(), b' '), (CHUNK_BIN, bin, 0)] {
+            let length = data.len().next_multiple_of(4);
+            chunks.extend_from_slice(&(length as u32).to_le_bytes());
+            chunks.extend_from_slice(&kind.to_le_bytes());
+            chunks.extend_from_slice(data);
+            chunks.resize(chunks.len() + length - data.len(), padding);
+        }
+        let mut file = GLB_MAGIC.to_vec();
+        file.extend_from_slice(&2u32.to_le_bytes());
+        file.extend_from_slice(&((GLB_HEADER_SIZE + chunks.len()) as u32).to_le_bytes());
+        file.extend(chunks);
+        file
+    }
+
+    fn parse(data: &[u8]) -> Result<Object, Box<dyn Error>> {
+        Object::parse_gltf(data, Path::new("test.gltf"), &ParseOptions::default())
+    }
+
+    fn triangles(object: &Object) -> Vec<(String, Triangle)> {
+        let mut triangles = Vec::new();
+        for submesh in object.submeshes.iter() {
+            let range = submesh.start as usize..(submesh.start + submesh.count) as usize;
+            for corners in object.indice[range].chunks_exact(3) {
+                let vertex = |i: usize| &object.vertex[corners[i] as usize];
+                let triangle = (
+                    [0, 1, 2].map(|i| vertex(i).position),
+                    [0, 1, 2].map(|i| vertex(i).normal),
+                    [0, 1, 2].map(|i| vertex(i).texture),
+                );
+                triangles.push((object.groups[submesh.group].name.clone(), triangle));
+            }
+        }
+        triangles
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    const FLAT: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    const NODE: &str = r#"[{ "name": "node", "mesh": 0 }]"#;
+
+    #[test]
+    fn data_uri() {
+        let data = triangle_buffer(FLAT, [0.0, 0.0, 1.0]);
+        let object = parse(embedded(&data, NODE, "[0]").as_bytes()).unwrap();
+        let triangles = triangles(&object);
+        assert_eq!(triangles.len(), 1);
+        let (group, (positions, normals, uvs)) = &triangles[0];
+        assert_eq!(group, "node");
+        assert_eq!(*positions, FLAT);
+        assert_eq!(*normals, [[0.0, 0.0, 1.0]; 3]);
+        // not flipped, gltf and scop both start at the top
+        assert_eq!(*uvs, [[0.0, 0.0], [1.0, 0.0], [0.25, 0.75]]);
+        let materials: Vec<&str> = object.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(materials, ["red"]);
+        assert_eq!(object.submeshes[0].material, Some(0));
+    }
+
+    #[test]
+    fn glb_bin_chunk() {
+        let data = triangle_buffer(FLAT, [0.0, 0.0, 1.0]);
+        let embedded = parse(embedded(&data, NODE, "[0]").as_bytes()).unwrap();
+        let json = gltf(
+            &format!(r#"{{ "byteLength": {} }}"#, data.len()),
+            NODE,
+            "[0]",
+        );
+        let file = glb(&json, &data);
+        assert_eq!(triangles(&parse(&file).unwrap()), triangles(&embedded));
+
+        for len in [3, GLB_HEADER_SIZE, GLB_HEADER_SIZE + 20, file.len() - 1] {
+            let mut truncated = file[..len].to_vec();
+            if len > 8 {
+                truncated[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+            }
+            assert!(parse(&truncated).is_err(), "{len} bytes");
+        }
+        // a buffer longer than the chunk and its padding
+        let length = data.len().next_multiple_of(4) + 1;
+        let json = gltf(&format!(r#"{{ "byteLength": {length} }}"#), NODE, "[0]");
+        assert!(parse(&glb(&json, &data)).is_err());
+    }
+
+    #[test]
+    fn transforms() {
+        // a sloped triangle, whose normal only stays perpendicular to it through the inverse
+        // transpose of non uniform scales
+        let sloped = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let data = triangle_buffer(sloped, [1.0 / 3f32.sqrt(); 3]);
+        let quarter = std::f32::consts::FRAC_1_SQRT_2;
+        let nodes = format!(
+            r#"[
+  {{ "name": "translated", "mesh": 0, "translation": [1, 2, 3] }},
+  {{ "name": "rotated", "mesh": 0, "rotation": [0, 0, {quarter}, {quarter}] }},
+  {{ "name": "scaled", "mesh": 0, "scale": [2, 1, 1] }},
+  {{ "name": "matrix", "mesh": 0, "matrix": [1, 0, 0, 0, 0, 2, 0, 0, 0, 0, 1, 0, 5, 0, 0, 1] }},
+  {{ "name": "mirrored", "mesh": 0, "scale": [-1, 1, 1] }},
+  {{ "name": "parent", "translation": [0, 0, 10], "children": [6] }},
+  {{ "name": "child", "mesh": 0, "scale": [2, 2, 2] }},
+  {{ "name": "trs", "mesh": 0, "translation": [1, 0, 0], "rotation": [0, 0, {quarter}, {quarter}], "scale": [2, 1, 1] }}
+]"#
+        );
+        let transforms: [(&str, fn([f32; 3]) -> [f32; 3]); 7] = [
+            ("translated", |[x, y, z]| [x + 1.0, y + 2.0, z + 3.0]),
+            ("rotated", |[x, y, z]| [-y, x, z]),
+            ("scaled", |[x, y, z]| [2.0 * x, y, z]),
+            ("matrix", |[x, y, z]| [x + 5.0, 2.0 * y, z]),
+            ("mirrored", |[x, y, z]| [-x, y, z]),
+            ("child", |[x, y, z]| [2.0 * x, 2.0 * y, 2.0 * z + 10.0]),
+            ("trs", |[x, y, z]| [1.0 - y, 2.0 * x, z]),
+        ];
+        let object = parse(embedded(&data, &nodes, "[0, 1, 2, 3, 4, 5, 7]").as_bytes()).unwrap();
+        let triangles = triangles(&object);
+        assert_eq!(triangles.len(), transforms.len());
+
+        for ((group, (positions, normals, _)), (name, transform)) in
+            triangles.iter().zip(transforms)
+        {
+            assert_eq!(group, name);
+            // the corners may be in another order, the winding is checked by the normal
+            for expected in sloped.map(transform) {
+                let close =
+                    |p: &[f32; 3]| p.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-5);
+                assert!(positions.iter().any(close), "{name}: {positions:?}");
+            }
+
+            let [a, b, c] = positions.map(|p| Vec3::from(&p));
+            let face = Vec3::cross(&(b - a), &(c - a)).normalize().to_array();
+            for normal in normals {
+                assert_close(*normal, face);
+            }
+        }
+    }
+
+    #[test]
+    fn strips_and_fans() {
+        // a unit square in both modes, the strip ends with a degenerate triangle
+        let positions = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ];
+        let mut data: Vec<u8> = positions
+            .iter()
+            .flatten()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        data.extend_from_slice(&[0, 1, 2, 3, 3, 0, 1, 3, 2]);
+        let json = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "nodes": [{{ "name": "strip", "mesh": 0 }}, {{ "name": "fan", "mesh": 1 }}],
+  "meshes": [
+    {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "mode": 5 }}] }},
+    {{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 2, "mode": 6 }}] }}
+  ],
+  "buffers": [{{ "byteLength": {}, "uri": "data:;base64,{}" }}],
+  "bufferViews": [{{ "buffer": 0, "byteLength": 57 }}],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" }},
+    {{ "bufferView": 0, "byteOffset": 48, "componentType": 5121, "count": 5, "type": "SCALAR" }},
+    {{ "bufferView": 0, "byteOffset": 53, "componentType": 5121, "count": 4, "type": "SCALAR" }}
+  ]
+}}"#,
+            data.len(),
+            base64(&data)
+        );
+        let object = parse(json.as_bytes()).unwrap();
+        let triangles = triangles(&object);
+        for name in ["strip", "fan"] {
+            let triangles: Vec<&Triangle> = triangles
+                .iter()
+                .filter(|(group, _)| group == name)
+                .map(|(_, triangle)| triangle)
+                .collect();
+            assert_eq!(triangles.len(), 2, "{name}");
+            let mut area = 0.0;
+            for (positions, _, _) in triangles {
+                let [a, b, c] = positions.map(|p| Vec3::from(&p));
+                let cross = Vec3::cross(&(b - a), &(c - a));
+                // all facing the same side
+                assert!(cross.z > 0.0, "{name}: {positions:?}");
+                area += cross.length() / 2.0;
+            }
+            assert!((area - 1.0).abs() < 1e-6, "{name}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let data = triangle_buffer(FLAT, [0.0, 0.0, 1.0]);
+        let valid = embedded(&data, NODE, "[0]");
+        let invalid = [
+            valid.replace("\"2.0\"", "\"1.0\""),
+            valid.replace(
+                "\"count\": 3, \"type\": \"VEC2\"",
+                "\"count\": 30, \"type\": \"VEC2\"",
+            ),
+            valid.replace("\"byteOffset\": 96", "\"byteOffset\": 4294967295"),
+            valid.replace("\"byteLength\": 96", "\"byteLength\": 4294967295"),
+            valid.replace("\"componentType\": 5123", "\"componentType\": 5124"),
+            valid.replace("\"mesh\": 0", "\"mesh\": 0, \"children\": [0]"),
+            valid.replace("\"material\": 0", "\"material\": 1"),
+            valid.replace(
+                &format!("\"byteLength\": {}", data.len()),
+                "\"byteLength\": 200",
+            ),
+            valid[..valid.len() - 1].to_string(),
+        ];
+        assert!(parse(valid.as_bytes()).is_ok());
+        for (i, json) in invalid.iter().enumerate() {
+            assert_ne!(*json, valid, "file {i} is not changed");
+            assert!(parse(json.as_bytes()).is_err(), "file {i}");
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use super::{
+    compression, error::ParseWarning, gltf, material::Material, object::ParseOptions,
+    png::PNG_SIGNATURE, texture::Texture, Object,
+};
+use std::{
+    cell::Cell,
+    error::Error,
+    fs,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    thread,
+};
+
+// files bigger than this are loaded in memory and parsed on every core
+const PARALLEL_THRESHOLD: u64 = 32 << 20;
+// the number of bytes read to detect the format of a file without a known extension
+const HEADER_SIZE: u64 = 512;
+
+// a mesh format, new formats are added to MESH_LOADERS
+pub trait MeshLoader: Sync {
+    fn name(&self) -> &'static str;
+
+    // lowercase, without the dot
+    fn extensions(&self) -> &'static [&'static str];
+
+    // whether the first bytes of a file are those of this format
+    fn detect(&self, _header: &[u8]) -> bool {
+        false
+    }
+
+    // `path` is where the file was read from, to find the files it references. The warnings
+    // are the problems skipped in lenient mode
+    fn parse(
+        &self,
+        data: &[u8],
+        path: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>>;
+
+    // the other files the object of the file at `path` is made from, the cache of the object
+    // is invalidated when they change
+    fn dependencies(&self, _path: &Path) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    // replaces the placeholder materials of the object parsed from the file at `path`, with
+    // the ones of the mtl files its mtllib statements name by default
+    fn load_materials(&self, object: &mut Object, path: &Path) {
+        object.load_materials(path.parent().unwrap_or(Path::new(".")));
+    }
+
+    // the file is decompressed when it is compressed. The loaders streaming the file
+    // regularly call `progress` with the number of bytes read so far and the size of the file,
+    // and a last time with the size of the file once it is read
+    fn load(
+        &self,
+        path: &Path,
+        options: &ParseOptions,
+        _progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        self.parse(&compression::read(path)?, path, options)
+    }
+}
+
+// an image format for the textures, new formats are added to IMAGE_LOADERS
+pub trait ImageLoader: Sync {
+    fn name(&self) -> &'static str;
+
+    // lowercase, without the dot
+    fn extensions(&self) -> &'static [&'static str];
+
+    // whether the first bytes of a file are those of this format
+    fn detect(&self, _header: &[u8]) -> bool {
+        false
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Texture, Box<dyn Error>>;
+}
+
+pub static MESH_LOADERS: &[&dyn MeshLoader] = &[
+    &ObjLoader,
+    &StlLoader,
+    &PlyLoader,
+    &GltfLoader,
+    &OffLoader,
+    &PointCloudLoader,
+];
+
+pub static IMAGE_LOADERS: &[&dyn ImageLoader] =
+    &[&NetpbmLoader, &PngLoader, &BmpLoader, &TgaLoader];
+
+// the loader of the mesh at `path`, from its extension or else from its first bytes, both
+// once decompressed. The files in an unknown format are parsed as obj, the format scop
+// started with
+pub fn mesh_loader(path: &Path) -> &'static dyn MeshLoader {
+    let name = compression::decompressed_path(path);
+    if let Some(loader) = MESH_LOADERS
+        .iter()
+        .find(|loader| has_extension(&name, loader.extensions()))
+    {
+        return *loader;
+    }
+    let header = read_header(path).unwrap_or_default();
+    MESH_LOADERS
+        .iter()
+        .find(|loader| loader.detect(&header))
+        .copied()
+        .unwrap_or(&ObjLoader)
+}
+
+// the loader of an image, from the extension of `path` when there is one or else from the
+// first bytes of the image
+pub fn image_loader(path: Option<&Path>, data: &[u8]) -> Option<&'static dyn ImageLoader> {
+    let by_extension = path.and_then(|path| {
+        IMAGE_LOADERS
+            .iter()
+            .find(|loader| has_extension(path, loader.extensions()))
+    });
+    by_extension
+        .or_else(|| IMAGE_LOADERS.iter().find(|loader| loader.detect(data)))
+        .copied()
+}
+
+pub fn load_image(path: &Path) -> Result<Texture, Box<dyn Error>> {
+    let data = compression::read(path)?;
+    decode_image(Some(&compression::decompressed_path(path)), &data)
+}
+
+pub fn decode_image(path: Option<&Path>, data: &[u8]) -> Result<Texture, Box<dyn Error>> {
+    let loader = image_loader(path, data).ok_or_else(|| {
+        format!(
+            "unsupported image format, the supported ones are {}",
+            supported_images()
+        )
+    })?;
+    loader
+        .parse(data)
+        .map_err(|e| format!("invalid {} image: {e}", loader.name()).into())
+}
+
+// the extensions of the supported formats, as "obj, stl, gltf/glb"
+pub fn supported_meshes() -> String {
+    let formats: Vec<String> = MESH_LOADERS
+        .iter()
+        .map(|loader| loader.extensions().join("/"))
+        .collect();
+    formats.join(", ")
+}
+
+pub fn supported_images() -> String {
+    let formats: Vec<String> = IMAGE_LOADERS
+        .iter()
+        .map(|loader| loader.extensions().join("/"))
+        .collect();
+    formats.join(", ")
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    compression::open(path)?
+        .take(HEADER_SIZE)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+pub struct ObjLoader;
+
+impl MeshLoader for ObjLoader {
+    fn name(&self) -> &'static str {
+        "obj"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["obj"]
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        _: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let parsed = if threads > 1 && data.len() as u64 >= PARALLEL_THRESHOLD {
+            Object::parse_parallel(data, options, threads, |_| {})
+        } else {
+            Object::parse_reader(data, options, |_| {})
+        };
+        Ok(parsed?)
+    }
+
+    // streams the file, only the big ones are kept in memory. Compressed files are always
+    // streamed, their progress is the one of the compressed data
+    fn load(
+        &self,
+        path: &Path,
+        options: &ParseOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        let (compression, objfile) = compression::open_raw(path)?;
+        let total = objfile.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut progress = |consumed| progress(consumed, total);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let parsed = if let Some(compression) = compression {
+            let read = Cell::new(0);
+            let reader = compression.decoder(CountingReader {
+                inner: objfile,
+                count: &read,
+            })?;
+            Object::parse_reader(BufReader::new(reader), options, |_| progress(read.get()))
+        } else if threads > 1 && total >= PARALLEL_THRESHOLD {
+            let data = fs::read(path)?;
+            Object::parse_parallel(&data, options, threads, &mut progress)
+        } else {
+            Object::parse_reader(BufReader::new(objfile), options, &mut progress)
+        };
+        progress(total);
+        Ok(parsed?)
+    }
+}
+
+// counts the bytes read from `inner`
+struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a Cell<u64>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+pub struct StlLoader;
+
+impl MeshLoader for StlLoader {
+    fn name(&self) -> &'static str {
+        "stl"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["stl"]
+    }
+
+    // only the ascii files have a signature
+    fn detect(&self, header: &[u8]) -> bool {
+        header.trim_ascii_start().starts_with(b"solid")
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        _: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        Ok((Object::parse_stl(data, options)?, Vec::new()))
+    }
+}
+
+pub struct PlyLoader;
+
+impl MeshLoader for PlyLoader {
+    fn name(&self) -> &'static str {
+        "ply"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ply"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"ply\n") || header.starts_with(b"ply\r\n")
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        _: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        Ok((Object::parse_ply(data, options)?, Vec::new()))
+    }
+}
+
+pub struct GltfLoader;
+
+impl MeshLoader for GltfLoader {
+    fn name(&self) -> &'static str {
+        "gltf"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gltf", "glb"]
+    }
+
+    // only the binary files have a signature
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"glTF")
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        path: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        Ok((Object::parse_gltf(data, path, options)?, Vec::new()))
+    }
+
+    // the external buffers and images
+    fn dependencies(&self, path: &Path) -> Vec<PathBuf> {
+        compression::read(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|data| gltf::external_files(&data, path))
+            .unwrap_or_default()
+    }
+
+    // the materials are in the gltf file itself
+    fn load_materials(&self, object: &mut Object, path: &Path) {
+        if object.materials.is_empty() {
+            return;
+        }
+        let materials = compression::read(path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|data| Material::parse_gltf(&data, path));
+        match materials {
+            Ok(materials) => {
+                object.set_materials(materials, path.parent().unwrap_or(Path::new(".")))
+            }
+            Err(e) => println!(
+                "gltf warning: failed to load the materials of {}: {e}",
+                path.display()
+            ),
+        }
+    }
+}
+
+pub struct OffLoader;
+
+impl MeshLoader for OffLoader {
+    fn name(&self) -> &'static str {
+        "off"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["off"]
+    }
+
+    // the keyword ends with OFF, as in COFF or STNOFF
+    fn detect(&self, header: &[u8]) -> bool {
+        header
+            .split(|b| b.is_ascii_whitespace())
+            .next()
+            .is_some_and(|keyword| keyword.ends_with(b"OFF"))
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        _: &Path,
+        options: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        Ok((Object::parse_off(data, options)?, Vec::new()))
+    }
+}
+
+pub struct PointCloudLoader;
+
+impl MeshLoader for PointCloudLoader {
+    fn name(&self) -> &'static str {
+        "point cloud"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xyz", "pts"]
+    }
+
+    fn parse(
+        &self,
+        data: &[u8],
+        _: &Path,
+        _: &ParseOptions,
+    ) -> Result<(Object, Vec<ParseWarning>), Box<dyn Error>> {
+        Ok((Object::parse_point_cloud(data)?, Vec::new()))
+    }
+}
+
+pub struct NetpbmLoader;
+
+impl ImageLoader for NetpbmLoader {
+    fn name(&self) -> &'static str {
+        "netpbm"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ppm", "pgm", "pbm", "pam", "pnm"]
+    }
+
+    // from P1 to P7
+    fn detect(&self, header: &[u8]) -> bool {
+        matches!(header, [b'P', b'1'..=b'7', next, ..] if next.is_ascii_whitespace())
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Texture, Box<dyn Error>> {
+        Texture::parse_netpbm(data)
+    }
+}
+
+pub struct PngLoader;
+
+impl ImageLoader for PngLoader {
+    fn name(&self) -> &'static str {
+        "png"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["png"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(PNG_SIGNATURE)
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Texture, Box<dyn Error>> {
+        Texture::parse_png(data)
+    }
+}
+
+pub struct BmpLoader;
+
+impl ImageLoader for BmpLoader {
+    fn name(&self) -> &'static str {
+        "bmp"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["bmp"]
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"BM")
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Texture, Box<dyn Error>> {
+        Texture::parse_bmp(data)
+    }
+}
+
+// tga files have no signature, they are only recognized by their extension
+pub struct TgaLoader;
+
+impl ImageLoader for TgaLoader {
+    fn name(&self) -> &'static str {
+        "tga"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tga"]
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Texture, Box<dyn Error>> {
+        Texture::parse_tga(data)
+    }
+}
@@ -0,0 +1,156 @@
+use super::{compression, loader, texture::Texture, Object};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub illum: u32,
+    pub map_kd: Option<String>,
+    pub map_bump: Option<String>,
+    pub diffuse_map: Option<Texture>,
+    // the directory map_kd and map_bump are relative to, the current one until the material
+    // is loaded
+    pub dir: PathBuf,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ambient: [0.0, 0.0, 0.0],
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            illum: 1,
+            map_kd: None,
+            map_bump: None,
+            diffuse_map: None,
+            dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Material {
+    pub fn parse_mtl(file: &str) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut materials: Vec<Material> = Vec::new();
+
+        for (line_number, line) in file.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line: Vec<&str> = line
+                .split_ascii_whitespace()
+                .take_while(|x| !x.starts_with('#'))
+                .collect();
+            if line.len() < 2 {
+                continue;
+            }
+
+            if line[0] == "newmtl" {
+                materials.push(Material {
+                    name: line[1..].join(" "),
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            let Some(material) = materials.last_mut() else {
+                return Err(format!("line {line_number}: statement before any newmtl").into());
+            };
+            match line[0] {
+                "Ka" => material.ambient = parse_color(&line, line_number)?,
+                "Kd" => material.diffuse = parse_color(&line, line_number)?,
+                "Ks" => material.specular = parse_color(&line, line_number)?,
+                "Ns" => material.shininess = line[1].parse()?,
+                "d" => material.dissolve = line[1].parse()?,
+                "Tr" => material.dissolve = 1.0 - line[1].parse::<f32>()?,
+                "illum" => material.illum = line[1].parse()?,
+                // options like -bm or -s come before the file name, which is always last
+                "map_Kd" => material.map_kd = line.last().map(|s| s.to_string()),
+                "map_Bump" | "map_bump" | "bump" => {
+                    material.map_bump = line.last().map(|s| s.to_string())
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(materials)
+    }
+
+    // loads the textures referenced by the material, paths are relative to `dir`
+    pub fn load_textures(&mut self, dir: &Path) {
+        self.dir = dir.to_path_buf();
+        let Some(map_kd) = &self.map_kd else {
+            return;
+        };
+        let path = dir.join(map_kd);
+        match loader::load_image(&path) {
+            Ok(texture) => self.diffuse_map = Some(texture),
+            Err(e) => println!(
+                "mtl warning: material {}: failed to load {}: {e}",
+                self.name,
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Object {
+    // replaces the placeholder materials created by usemtl statements with the definitions
+    // found in the mtllib files, which are looked up relative to `dir`
+    pub fn load_materials(&mut self, dir: &Path) {
+        let mut definitions = Vec::new();
+        for mtllib in self.mtllibs.iter() {
+            let path = dir.join(mtllib);
+            let materials = compression::read(&path)
+                .map_err(Box::<dyn Error>::from)
+                .and_then(|file| Material::parse_mtl(std::str::from_utf8(&file)?));
+            match materials {
+                Ok(materials) => definitions.extend(materials),
+                Err(e) => println!("mtl warning: failed to load {}: {e}", path.display()),
+            }
+        }
+        self.set_materials(definitions, dir);
+    }
+
+    // replaces the placeholder materials with the definitions of the same name, whose
+    // textures are looked up relative to `dir`. A later definition overrides an earlier one
+    pub fn set_materials(&mut self, definitions: Vec<Material>, dir: &Path) {
+        let definitions: HashMap<String, Material> = definitions
+            .into_iter()
+            .map(|m| (m.name.clone(), m))
+            .collect();
+        for material in self.materials.iter_mut() {
+            match definitions.get(&material.name) {
+                Some(definition) => {
+                    *material = definition.clone();
+                    material.load_textures(dir);
+                }
+                None => println!(
+                    "mtl warning: material {} is not defined, using the default one",
+                    material.name
+                ),
+            }
+        }
+    }
+}
+
+fn parse_color(line: &[&str], line_number: usize) -> Result<[f32; 3], Box<dyn Error>> {
+    match line.len() {
+        // a single value is used for the three channels
+        2 => {
+            let c = line[1].parse()?;
+            Ok([c, c, c])
+        }
+        4 => Ok([line[1].parse()?, line[2].parse()?, line[3].parse()?]),
+        _ => Err(format!("line {line_number}: expected (r, g, b) format").into()),
+    }
+}
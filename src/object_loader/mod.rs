@@ -1,6 +1,26 @@
+pub mod bmp;
+pub mod cache;
+pub mod compression;
+pub mod error;
+pub mod export;
+pub mod gltf;
+pub mod json;
+pub mod loader;
+pub mod material;
+pub mod normals;
 pub mod object;
+pub mod off;
+pub mod parallel;
+pub mod ply;
+pub mod png;
+pub mod pointcloud;
+pub mod stl;
 pub mod texture;
+pub mod tga;
+pub mod triangulate;
+pub mod uv_grid;
 use crate::math::Vec3;
+use material::Material;
 use std::hash::Hash;
 use vulkano::{buffer::BufferContents, pipeline::graphics::vertex_input::Vertex};
 
@@ -10,6 +30,56 @@ pub struct Object {
     pub indice: Vec<u32>,
     pub size: Vec3,
     pub center: Vec3,
+    pub mtllibs: Vec<String>,
+    pub materials: Vec<Material>,
+    pub submeshes: Vec<Submesh>,
+    pub groups: Vec<Group>,
+    pub topology: Topology,
+}
+
+impl Object {
+    // lists the groups with the number of triangles or points drawn in each of them
+    pub fn print_groups(&self) {
+        println!("groups:");
+        for (i, group) in self.groups.iter().enumerate() {
+            let count: u32 = self
+                .submeshes
+                .iter()
+                .filter(|s| s.group == i)
+                .map(|s| s.count)
+                .sum();
+            match self.topology {
+                Topology::Triangles => println!("  {i}: {} ({} triangles)", group.name, count / 3),
+                Topology::Points => println!("  {i}: {} ({count} points)", group.name),
+            }
+        }
+    }
+}
+
+// how the vertices are drawn, the submeshes of point clouds are ranges of vertices as they
+// have no indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Triangles,
+    Points,
+}
+
+// a contiguous range of the index buffer drawn with the same material and belonging to the
+// same group
+#[derive(Debug, Clone, Copy)]
+pub struct Submesh {
+    pub material: Option<usize>,
+    pub group: usize,
+    pub start: u32,
+    pub count: u32,
+}
+
+// a named part of the object, from the o and g statements
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    pub min: Vec3,
+    pub max: Vec3,
 }
 
 #[derive(BufferContents, Vertex, Debug, Clone, Copy, Default)]
@@ -38,11 +108,13 @@ impl PartialEq for Vertexxx {
             .position
             .iter()
             .chain(self.normal.iter())
+            .chain(self.color.iter())
             .chain(self.texture.iter());
         let iter_other = other
             .position
             .iter()
             .chain(other.normal.iter())
+            .chain(other.color.iter())
             .chain(other.texture.iter());
         for (s, o) in iter_self.zip(iter_other) {
             if s.to_bits() != o.to_bits() {
@@ -61,6 +133,7 @@ impl Hash for Vertexxx {
             .position
             .iter()
             .chain(self.normal.iter())
+            .chain(self.color.iter())
             .chain(self.texture.iter())
         {
             v.to_bits().hash(state);
@@ -0,0 +1,106 @@
+use super::Vertexxx;
+use crate::{math::Vec3, vec3};
+
+// a triangle waiting for its normals before being added to the object
+#[derive(Debug, Clone, Copy)]
+pub struct Triangle {
+    pub corners: [Vertexxx; 3],
+    // index of the position of each corner, corners sharing a position can share a normal
+    pub positions: [usize; 3],
+    // 0 means the triangle is not smoothed (s off)
+    pub smoothing_group: u32,
+    pub has_normal: bool,
+}
+
+// sets the normals of the triangles that don't have any
+// corners are smoothed with the other triangles of their smoothing group sharing the same
+// position, weighted by the angle of each triangle at that corner. Triangles outside of any
+// smoothing group keep their flat normal, unless `crease_angle` is set: they are then smoothed
+// with their neighbours whose normal is less than `crease_angle` radians away from theirs
+pub fn generate_normals(
+    triangles: &mut [Triangle],
+    position_count: usize,
+    crease_angle: Option<f32>,
+) {
+    let face_normals: Vec<Vec3> = triangles.iter().map(calculate_normal).collect();
+
+    // triangles touching each position, stored contiguously
+    let mut offsets = vec![0; position_count + 1];
+    for triangle in triangles.iter() {
+        for &p in triangle.positions.iter() {
+            offsets[p + 1] += 1;
+        }
+    }
+    for i in 0..position_count {
+        offsets[i + 1] += offsets[i];
+    }
+    let mut adjacency = vec![(0, 0); offsets[position_count]];
+    let mut filled = offsets.clone();
+    for (t, triangle) in triangles.iter().enumerate() {
+        for (c, &p) in triangle.positions.iter().enumerate() {
+            adjacency[filled[p]] = (t, c);
+            filled[p] += 1;
+        }
+    }
+
+    let min_cos = crease_angle.map(f32::cos);
+    let mut normals = Vec::with_capacity(triangles.len());
+    for (t, triangle) in triangles.iter().enumerate() {
+        if triangle.has_normal {
+            normals.push(None);
+            continue;
+        }
+
+        let face_normal = face_normals[t];
+        let mut corner_normals = [face_normal.to_array(); 3];
+        if triangle.smoothing_group != 0 || min_cos.is_some() {
+            for (corner_normal, &p) in corner_normals.iter_mut().zip(triangle.positions.iter()) {
+                let mut sum = vec3!(0.0);
+                for &(other, corner) in adjacency[offsets[p]..offsets[p + 1]].iter() {
+                    let other_normal = face_normals[other];
+                    let smoothed = match (triangle.smoothing_group, min_cos) {
+                        (0, Some(min_cos)) => {
+                            triangles[other].smoothing_group == 0
+                                && Vec3::dot(&face_normal, &other_normal) >= min_cos
+                        }
+                        (group, _) => triangles[other].smoothing_group == group,
+                    };
+                    if smoothed && other_normal.length().is_finite() {
+                        sum += other_normal * corner_angle(&triangles[other], corner);
+                    }
+                }
+                if sum.length() > f32::EPSILON {
+                    *corner_normal = sum.normalize().to_array();
+                }
+            }
+        }
+        normals.push(Some(corner_normals));
+    }
+
+    for (triangle, normals) in triangles.iter_mut().zip(normals) {
+        if let Some(normals) = normals {
+            for (corner, normal) in triangle.corners.iter_mut().zip(normals) {
+                corner.normal = normal;
+            }
+        }
+    }
+}
+
+fn calculate_normal(triangle: &Triangle) -> Vec3 {
+    let [v1, v2, v3] = triangle.corners.map(|c| Vec3::from(&c.position));
+
+    Vec3::normalize(&Vec3::cross(&(v2 - v1), &(v3 - v2)))
+}
+
+fn corner_angle(triangle: &Triangle, corner: usize) -> f32 {
+    let p = Vec3::from(&triangle.corners[corner].position);
+    let a = Vec3::from(&triangle.corners[(corner + 1) % 3].position) - p;
+    let b = Vec3::from(&triangle.corners[(corner + 2) % 3].position) - p;
+    let cos = Vec3::dot(&a, &b) / (a.length() * b.length());
+
+    if cos.is_finite() {
+        cos.clamp(-1.0, 1.0).acos()
+    } else {
+        0.0
+    }
+}
@@ -1,117 +1,170 @@
-use super::{Object, Vertexxx};
-use crate::math::Vec3;
-use std::{collections::HashMap, error::Error, usize};
+use super::{
+    error::{ObjError, ObjErrorKind, ParseWarning},
+    material::Material,
+    normals::{generate_normals, Triangle},
+    triangulate::triangulate,
+    Group, Object, Submesh, Topology, Vertexxx,
+};
+use crate::{math::Vec3, vec3};
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+    io::BufRead,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // fail on the first problem instead of skipping it with a warning
+    pub strict: bool,
+    // when the file has no smoothing groups, faces without normals are smoothed with their
+    // neighbours whose normal is less than this angle (in radians) away
+    pub crease_angle: Option<f32>,
+}
+
+// number of bytes read between two progress reports
+const PROGRESS_STEP: u64 = 1 << 20;
+
+// statements of the obj format that are valid but not rendered (free-form geometry, lines,
+// points, ...), they are skipped with a warning
+const UNSUPPORTED_STATEMENTS: [&str; 27] = [
+    "vp",
+    "l",
+    "p",
+    "cstype",
+    "deg",
+    "bmat",
+    "step",
+    "curv",
+    "curv2",
+    "surf",
+    "parm",
+    "trim",
+    "hole",
+    "scrv",
+    "sp",
+    "end",
+    "con",
+    "mg",
+    "bevel",
+    "c_interp",
+    "d_interp",
+    "lod",
+    "usemap",
+    "maplib",
+    "shadow_obj",
+    "trace_obj",
+    "ctech",
+];
 
 impl Object {
-    pub fn parse(object: &str) -> Result<Self, Box<dyn Error>> {
-        let mut v: Vec<[[f32; 3]; 2]> = vec![[[0.0, 0.0, 0.0], [0.0, 0.0, 0.0]]];
-        let mut vt: Vec<[f32; 2]> = vec![[0.0, 0.0]];
-        let mut vn: Vec<[f32; 3]> = vec![[0.0, 0.0, 0.0]];
+    // parses the object while it is being read, without keeping the file in memory
+    // `progress` is regularly called with the number of bytes consumed so far
+    pub fn parse_reader<R: BufRead>(
+        mut reader: R,
+        options: &ParseOptions,
+        mut progress: impl FnMut(u64),
+    ) -> Result<(Self, Vec<ParseWarning>), ObjError> {
+        let mut parser = ObjParser::new(options);
+        let mut buffer = Vec::new();
+        let mut consumed = 0;
+        let mut last_report = 0;
+        let mut line_number = 0;
 
-        let mut unique_vertices: HashMap<Vertexxx, u32> = HashMap::new();
-        let mut obj = Object {
-            vertex: Vec::from([Vertexxx::default()]),
-            indice: Vec::new(),
-            size: Vec3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            },
-            center: Vec3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            },
-        };
+        loop {
+            buffer.clear();
+            let read = reader
+                .read_until(b'\n', &mut buffer)
+                .map_err(|e| ObjError::new(ObjErrorKind::Io, line_number + 1, 1, &e.to_string()))?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+            consumed += read as u64;
+
+            // the invalid characters can only be in comments or names, they are replaced
+            // rather than failing the whole file
+            let raw = String::from_utf8_lossy(trim_line_end(&buffer));
+            parser.parse_line(&raw, line_number)?;
 
-        for (line_number, line) in object.lines().enumerate() {
-            let line_number = line_number + 1;
-            let line: Vec<&str> = line
-                .split_ascii_whitespace()
-                .take_while(|x| !x.contains("#"))
-                .collect();
-            if line.len() < 2 {
-                continue;
+            if consumed - last_report >= PROGRESS_STEP {
+                progress(consumed);
+                last_report = consumed;
             }
-            match line[0] {
-                "v" => {
-                    if line.len() == 4 {
-                        v.push([
-                            [line[1].parse()?, line[2].parse()?, line[3].parse()?],
-                            [1.0, 1.0, 1.0],
-                        ]);
-                    } else if line.len() == 7 {
-                        let mut color: [f32; 3] =
-                            [line[4].parse()?, line[5].parse()?, line[6].parse()?];
-                        for c in color.iter_mut() {
-                            if *c > 1.0 {
-                                *c = *c / 255.0;
-                            }
-                        }
-                        v.push([
-                            [line[1].parse()?, line[2].parse()?, line[3].parse()?],
-                            color,
-                        ]);
-                    } else {
-                        return Err(format!(
-                            "line {line_number}: expected (x, y, z [, r, g, b]) format"
-                        )
-                        .into());
-                    }
-                }
-                "vt" => {
-                    if line.len() < 3 || line.len() > 4 {
-                        return Err(
-                            format!("line {line_number}: expected (u, v, [w]) format").into()
-                        );
-                    }
-                    let tmp: [f32; 2] = [line[1].parse()?, line[2].parse()?];
-                    vt.push([tmp[0], 1.0 - tmp[1]]);
-                }
-                "vn" => {
-                    if line.len() != 4 {
-                        return Err(format!("line {line_number}: expected (x, y, z) format").into());
-                    }
-                    vn.push([line[1].parse()?, line[2].parse()?, line[3].parse()?]);
+        }
+        progress(consumed);
+
+        Ok(parser.finish())
+    }
+
+    // pushes the faces added since the start of `submesh` as a new submesh and starts the
+    // next one right after them, `triangle_count` is the number of triangles parsed so far
+    fn close_submesh(&mut self, submesh: &mut Submesh, triangle_count: usize) {
+        let end = triangle_count as u32 * 3;
+        submesh.count = end - submesh.start;
+        if submesh.count > 0 {
+            self.submeshes.push(*submesh);
+        }
+        submesh.start = end;
+    }
+
+    fn group_index(&mut self, name: &str) -> usize {
+        match self.groups.iter().position(|g| g.name == name) {
+            Some(i) => i,
+            None => {
+                self.groups.push(Group {
+                    name: name.to_string(),
+                    min: vec3!(f32::MAX),
+                    max: vec3!(f32::MIN),
+                });
+                self.groups.len() - 1
+            }
+        }
+    }
+
+    // computes the bounding box of every group and removes the ones without any face
+    fn set_groups_bounds(&mut self) {
+        for submesh in self.submeshes.iter() {
+            let group = &mut self.groups[submesh.group];
+            let range = submesh.start as usize..(submesh.start + submesh.count) as usize;
+            for &i in self.indice[range].iter() {
+                let position = Vec3::from(&self.vertex[i as usize].position);
+                for axis in 0..3 {
+                    group.min[axis] = group.min[axis].min(position[axis]);
+                    group.max[axis] = group.max[axis].max(position[axis]);
                 }
-                "f" => {
-                    if line.len() == 4 {
-                        let (v1, has_normal, _has_texture) = parse_face_el(line[1], &v, &vt, &vn)?;
-                        let (v2, _, _) = parse_face_el(line[2], &v, &vt, &vn)?;
-                        let (v3, _, _) = parse_face_el(line[3], &v, &vt, &vn)?;
-
-                        handle_face(v1, v2, v3, &mut obj, &mut unique_vertices, has_normal);
-                    } else if line.len() == 5 {
-                        let (v1, has_normal, _has_texture) = parse_face_el(line[1], &v, &vt, &vn)?;
-                        let (v2, _, _) = parse_face_el(line[2], &v, &vt, &vn)?;
-                        let (v3, _, _) = parse_face_el(line[3], &v, &vt, &vn)?;
-                        let (v4, _, _) = parse_face_el(line[4], &v, &vt, &vn)?;
-
-                        handle_face(v1, v2, v3, &mut obj, &mut unique_vertices, has_normal);
-                        handle_face(v1, v3, v4, &mut obj, &mut unique_vertices, has_normal);
-                    } else if line.len() > 5 {
-                        println!(
-                            "obj warning: line {}: faces with more than 5 vertices are not handled and are ignored",
-                            line_number
-                        );
-                    } else {
-                        return Err(
-                            format!("line {line_number}: expected (a, b, c [, d]) format").into(),
-                        );
+            }
+        }
+
+        // a group is kept when it has faces, even when none of their positions is a number
+        let mut new_index = vec![None; self.groups.len()];
+        for submesh in self.submeshes.iter() {
+            new_index[submesh.group] = Some(0);
+        }
+        let mut kept = 0;
+        for (i, group) in self.groups.iter_mut().enumerate() {
+            if let Some(index) = &mut new_index[i] {
+                *index = kept;
+                kept += 1;
+                // the axes on which no position is a number
+                for axis in 0..3 {
+                    if group.min[axis] > group.max[axis] {
+                        group.min[axis] = 0.0;
+                        group.max[axis] = 0.0;
                     }
                 }
-                "#" | "o" | "s" | "mtllib" | "usemtl" | "g" => continue,
-                _ => return Err(format!("line {line_number}: invalid line start").into()),
             }
         }
-
-        let (vmin, vmax) = obj.set_obj_size_and_center();
-
-        if vt.len() == 1 {
-            obj.set_naive_texture_coordinate(vmin, vmax);
+        for submesh in self.submeshes.iter_mut() {
+            if let Some(index) = new_index[submesh.group] {
+                submesh.group = index;
+            }
         }
-
-        Ok(obj)
+        let mut i = 0;
+        self.groups.retain(|_| {
+            i += 1;
+            new_index[i - 1].is_some()
+        });
     }
 
     fn set_obj_size_and_center(&mut self) -> (Vec3, Vec3) {
@@ -160,102 +213,602 @@ impl Object {
     }
 }
 
-fn parse_face_el(
-    face: &str,
-    v: &[[[f32; 3]; 2]],
-    vt: &[[f32; 2]],
-    vn: &[[f32; 3]],
-) -> Result<(Vertexxx, bool, bool), Box<dyn Error>> {
-    let el: Vec<&str> = face.split('/').collect();
-
-    match el.len() {
-        // vertices only
-        1 => {
-            let vertex = Vertexxx {
-                position: v[convert_index(el[0], v.len())?][0],
-                color: v[convert_index(el[0], v.len())?][1],
-                ..Default::default()
-            };
-            Ok((vertex, false, false))
+// the v, vt and vn elements, in the order of the file
+#[derive(Default)]
+pub(super) struct VertexLists {
+    pub(super) v: Vec<[[f32; 3]; 2]>,
+    pub(super) vt: Vec<[f32; 2]>,
+    pub(super) vn: Vec<[f32; 3]>,
+}
+
+impl VertexLists {
+    // parses a v, vt or vn statement, the element is still added when it is invalid so that
+    // the indices of the next ones stay right
+    pub(super) fn parse(&mut self, line: &Line) -> Result<(), ObjError> {
+        match line.token(0) {
+            "v" => {
+                let vertex = parse_vertex(line);
+                self.v
+                    .push(*vertex.as_ref().unwrap_or(&[[0.0; 3], [1.0; 3]]));
+                vertex.map(|_| ())
+            }
+            "vt" => {
+                let texture = parse_texture(line);
+                self.vt.push(*texture.as_ref().unwrap_or(&[0.0, 1.0]));
+                texture.map(|_| ())
+            }
+            _ => {
+                let normal = parse_normal(line);
+                self.vn.push(*normal.as_ref().unwrap_or(&[0.0; 3]));
+                normal.map(|_| ())
+            }
         }
-        // vertices and textures
-        2 => {
-            let vertex = Vertexxx {
-                position: v[convert_index(el[0], v.len())?][0],
-                color: v[convert_index(el[0], v.len())?][1],
-                texture: vt[convert_index(el[1], vt.len())?],
-                ..Default::default()
-            };
-            Ok((vertex, false, false))
+    }
+
+    pub(super) fn counts(&self) -> [usize; 3] {
+        [self.v.len(), self.vt.len(), self.vn.len()]
+    }
+
+    // the vertex of a face element, `texture` and `normal` are optional indices
+    pub(super) fn face_vertex(
+        &self,
+        position: usize,
+        texture: Option<usize>,
+        normal: Option<usize>,
+    ) -> (Vertexxx, usize, bool) {
+        let mut vertex = Vertexxx {
+            position: self.v[position][0],
+            color: self.v[position][1],
+            ..Default::default()
+        };
+        if let Some(i) = texture {
+            vertex.texture = self.vt[i];
         }
-        3 => {
-            // vertices, normals and textures
-            if el[1] != "" {
-                let vertex = Vertexxx {
-                    position: v[convert_index(el[0], v.len())?][0],
-                    color: v[convert_index(el[0], v.len())?][1],
-                    texture: vt[convert_index(el[1], vt.len())?],
-                    normal: vn[convert_index(el[2], vn.len())?],
-                };
-                Ok((vertex, true, true))
-            // vertices and normals
-            } else {
-                let vertex = Vertexxx {
-                    position: v[convert_index(el[0], v.len())?][0],
-                    color: v[convert_index(el[0], v.len())?][1],
-                    normal: vn[convert_index(el[2], vn.len())?],
-                    ..Default::default()
+        if let Some(i) = normal {
+            vertex.normal = self.vn[i];
+        }
+        (vertex, position, normal.is_some())
+    }
+}
+
+// state of the parsing, fed line by line
+pub(super) struct ObjParser<'a> {
+    options: &'a ParseOptions,
+    pub(super) lists: VertexLists,
+    obj: Object,
+    submesh: Submesh,
+    pub(super) triangles: Vec<Triangle>,
+    smoothing_group: u32,
+    has_smoothing_groups: bool,
+    warnings: Vec<ParseWarning>,
+    // buffers reused from one line to the next
+    tokens: Vec<(usize, usize)>,
+    // vertex, index of its position and whether it has a normal
+    pub(super) polygon: Vec<(Vertexxx, usize, bool)>,
+    positions: Vec<Vec3>,
+}
+
+impl<'a> ObjParser<'a> {
+    pub(super) fn new(options: &'a ParseOptions) -> Self {
+        let mut obj = Object {
+            vertex: Vec::from([Vertexxx::default()]),
+            indice: Vec::new(),
+            size: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            center: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            mtllibs: Vec::new(),
+            materials: Vec::new(),
+            submeshes: Vec::new(),
+            groups: Vec::new(),
+            topology: Topology::Triangles,
+        };
+        let submesh = Submesh {
+            material: None,
+            group: obj.group_index("default"),
+            start: 0,
+            count: 0,
+        };
+
+        Self {
+            options,
+            lists: VertexLists {
+                v: vec![[[0.0, 0.0, 0.0], [0.0, 0.0, 0.0]]],
+                vt: vec![[0.0, 0.0]],
+                vn: vec![[0.0, 0.0, 0.0]],
+            },
+            obj,
+            submesh,
+            triangles: Vec::new(),
+            smoothing_group: 0,
+            has_smoothing_groups: false,
+            warnings: Vec::new(),
+            tokens: Vec::new(),
+            polygon: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    pub(super) fn parse_line(&mut self, raw: &str, number: usize) -> Result<(), ObjError> {
+        let mut tokens = std::mem::take(&mut self.tokens);
+        tokenize(raw.as_bytes(), &mut tokens);
+        let line = Line {
+            raw,
+            number,
+            tokens: &tokens,
+        };
+
+        let result = if line.len() < 2 {
+            Ok(())
+        } else {
+            self.parse_statement(&line)
+        };
+        self.tokens = tokens;
+
+        self.report(result)
+    }
+
+    // in lenient mode, problems become warnings and the faulty statement is skipped
+    pub(super) fn report(&mut self, result: Result<(), ObjError>) -> Result<(), ObjError> {
+        match result {
+            Err(e) if !self.options.strict => {
+                self.warnings.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn parse_statement(&mut self, line: &Line) -> Result<(), ObjError> {
+        match line.token(0) {
+            "v" | "vt" | "vn" => self.lists.parse(line),
+            "f" => self.parse_face(line),
+            "s" => {
+                self.smoothing_group = match line.token(1) {
+                    "off" => 0,
+                    // as some exporters write it, the same as s 1
+                    "on" => 1,
+                    group => line.parse(group)?,
                 };
-                Ok((vertex, true, false))
+                self.has_smoothing_groups |= self.smoothing_group != 0;
+                Ok(())
+            }
+            "mtllib" => {
+                self.obj
+                    .mtllibs
+                    .extend(line.tokens_from(1).map(|s| s.to_string()));
+                Ok(())
+            }
+            "usemtl" => {
+                self.use_material(Some(line.rest(1)));
+                Ok(())
+            }
+            "o" | "g" => {
+                self.start_group(line.rest(1));
+                Ok(())
+            }
+            "#" => Ok(()),
+            statement if UNSUPPORTED_STATEMENTS.contains(&statement) => {
+                Err(line.error(ObjErrorKind::UnsupportedStatement, statement))
             }
+            statement => Err(line.error(ObjErrorKind::UnknownStatement, statement)),
         }
-        _ => Err("Error parsing face".into()),
     }
+
+    // the next faces are drawn with the material `name`, or with the default one
+    pub(super) fn use_material(&mut self, name: Option<&str>) {
+        let obj = &mut self.obj;
+        let material = name.map(|name| {
+            match obj.materials.iter().position(|m| m.name == name) {
+                Some(i) => i,
+                None => {
+                    // placeholder until the mtllib files are loaded
+                    obj.materials.push(Material {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                    obj.materials.len() - 1
+                }
+            }
+        });
+        obj.close_submesh(&mut self.submesh, self.triangles.len());
+        self.submesh.material = material;
+    }
+
+    // the next faces belong to the group `name`
+    pub(super) fn start_group(&mut self, name: &str) {
+        let group = self.obj.group_index(name);
+        self.obj
+            .close_submesh(&mut self.submesh, self.triangles.len());
+        self.submesh.group = group;
+    }
+
+    fn parse_face(&mut self, line: &Line) -> Result<(), ObjError> {
+        if line.len() < 4 {
+            return Err(line.error(ObjErrorKind::MalformedFace, line.token(0)));
+        }
+        self.polygon.clear();
+        for el in line.tokens_from(1) {
+            self.polygon.push(parse_face_el(el, line, &self.lists)?);
+        }
+        // a face mixing v//vn and v elements is kept with generated normals, after a warning
+        let has_normal = self.polygon[0].2;
+        if let Some(i) = self.polygon.iter().position(|v| v.2 != has_normal) {
+            let el = line.tokens_from(1).nth(i).unwrap_or("");
+            self.report(Err(line.error(ObjErrorKind::MalformedFace, el)))?;
+        }
+        self.push_polygon();
+        Ok(())
+    }
+
+    // triangulates the face in `polygon`, its normals are generated unless all of its
+    // vertices have one
+    pub(super) fn push_polygon(&mut self) {
+        let polygon = &self.polygon;
+        let has_normal = polygon.iter().all(|(_, _, has_normal)| *has_normal);
+
+        let mut push = |[a, b, c]: [usize; 3]| {
+            self.triangles.push(Triangle {
+                corners: [polygon[a].0, polygon[b].0, polygon[c].0],
+                positions: [polygon[a].1, polygon[b].1, polygon[c].1],
+                smoothing_group: self.smoothing_group,
+                has_normal,
+            })
+        };
+        if polygon.len() == 3 {
+            push([0, 1, 2]);
+        } else {
+            self.positions.clear();
+            self.positions.extend(
+                polygon
+                    .iter()
+                    .map(|(vertex, _, _)| Vec3::from(&vertex.position)),
+            );
+            triangulate(&self.positions).into_iter().for_each(push);
+        }
+    }
+
+    pub(super) fn finish(mut self) -> (Object, Vec<ParseWarning>) {
+        let mut obj = self.obj;
+        obj.close_submesh(&mut self.submesh, self.triangles.len());
+
+        let crease_angle = if self.has_smoothing_groups {
+            None
+        } else {
+            self.options.crease_angle
+        };
+        generate_normals(&mut self.triangles, self.lists.v.len(), crease_angle);
+        let mut unique_vertices =
+            VertexMap::with_capacity_and_hasher(self.lists.v.len(), Default::default());
+        obj.indice.reserve(self.triangles.len() * 3);
+        for triangle in self.triangles {
+            handle_face(triangle.corners, &mut obj, &mut unique_vertices);
+        }
+
+        obj.set_groups_bounds();
+        let (vmin, vmax) = obj.set_obj_size_and_center();
+
+        if self.lists.vt.len() == 1 {
+            obj.set_naive_texture_coordinate(vmin, vmax);
+        }
+
+        (obj, self.warnings)
+    }
+}
+
+// removes the line ending (\n or \r\n) of a line
+pub(super) fn trim_line_end(mut raw: &[u8]) -> &[u8] {
+    if let [rest @ .., b'\n'] = raw {
+        raw = rest;
+    }
+    if let [rest @ .., b'\r'] = raw {
+        raw = rest;
+    }
+    raw
 }
 
-fn convert_index(i: &str, size: usize) -> Result<usize, Box<dyn Error>> {
-    let signed: i32 = i.parse()?;
-    if signed < 0 {
-        match size.checked_sub(signed.abs() as usize) {
-            Some(unsigned) => Ok(unsigned),
-            None => Err("index error while parsing face".into()),
+// fills `tokens` with the start and end of the whitespace separated tokens of `raw`, up to
+// the first one containing a comment
+pub(super) fn tokenize(raw: &[u8], tokens: &mut Vec<(usize, usize)>) {
+    tokens.clear();
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
         }
+        let start = i;
+        let mut comment = false;
+        while i < raw.len() && !raw[i].is_ascii_whitespace() {
+            comment |= raw[i] == b'#';
+            i += 1;
+        }
+        if comment {
+            break;
+        }
+        tokens.push((start, i));
+    }
+}
+
+fn parse_vertex(line: &Line) -> Result<[[f32; 3]; 2], ObjError> {
+    // the optional w weight of rational curves is ignored
+    if line.len() == 4 || line.len() == 5 {
+        Ok([
+            [
+                line.parse(line.token(1))?,
+                line.parse(line.token(2))?,
+                line.parse(line.token(3))?,
+            ],
+            [1.0, 1.0, 1.0],
+        ])
+    } else if line.len() == 7 {
+        let mut color: [f32; 3] = [
+            line.parse(line.token(4))?,
+            line.parse(line.token(5))?,
+            line.parse(line.token(6))?,
+        ];
+        for c in color.iter_mut() {
+            if *c > 1.0 {
+                *c /= 255.0;
+            }
+        }
+        Ok([
+            [
+                line.parse(line.token(1))?,
+                line.parse(line.token(2))?,
+                line.parse(line.token(3))?,
+            ],
+            color,
+        ])
+    } else {
+        Err(line.error(
+            ObjErrorKind::MalformedStatement("(x, y, z [, w] | x, y, z, r, g, b)"),
+            line.token(0),
+        ))
+    }
+}
+
+fn parse_texture(line: &Line) -> Result<[f32; 2], ObjError> {
+    if line.len() > 4 {
+        return Err(line.error(
+            ObjErrorKind::MalformedStatement("(u [, v, w])"),
+            line.token(0),
+        ));
+    }
+    let u: f32 = line.parse(line.token(1))?;
+    let v: f32 = if line.len() > 2 {
+        line.parse(line.token(2))?
     } else {
-        Ok(signed as usize)
+        0.0
+    };
+    Ok([u, 1.0 - v])
+}
+
+fn parse_normal(line: &Line) -> Result<[f32; 3], ObjError> {
+    if line.len() != 4 {
+        return Err(line.error(ObjErrorKind::MalformedStatement("(x, y, z)"), line.token(0)));
     }
+    Ok([
+        line.parse(line.token(1))?,
+        line.parse(line.token(2))?,
+        line.parse(line.token(3))?,
+    ])
+}
+
+// a line of the file being parsed, split in tokens (up to the first comment)
+pub(super) struct Line<'a> {
+    pub(super) raw: &'a str,
+    pub(super) number: usize,
+    // start and end of each token in `raw`
+    pub(super) tokens: &'a [(usize, usize)],
 }
 
-fn handle_face(
-    mut v1: Vertexxx,
-    mut v2: Vertexxx,
-    mut v3: Vertexxx,
-    obj: &mut Object,
-    unique_vertices: &mut HashMap<Vertexxx, u32>,
-    has_normal: bool,
-) {
-    if !has_normal {
-        let normal = calculate_normal(&v1, &v2, &v3);
-
-        v1.normal = normal;
-        v2.normal = normal;
-        v3.normal = normal;
-    }
-
-    for v in [v1, v2, v3] {
-        if unique_vertices.contains_key(&v) {
-            obj.indice.push(unique_vertices[&v]);
+impl<'a> Line<'a> {
+    pub(super) fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub(super) fn token(&self, i: usize) -> &'a str {
+        let (start, end) = self.tokens[i];
+        &self.raw[start..end]
+    }
+
+    pub(super) fn tokens_from(&self, i: usize) -> impl Iterator<Item = &'a str> + '_ {
+        (i..self.len()).map(|i| self.token(i))
+    }
+
+    // the tokens from the i-th one, with the whitespaces between them (names can have spaces)
+    fn rest(&self, i: usize) -> &'a str {
+        &self.raw[self.tokens[i].0..self.tokens[self.len() - 1].1]
+    }
+
+    // `token` has to be a slice of the line for the column to be right
+    fn error(&self, kind: ObjErrorKind, token: &str) -> ObjError {
+        let offset = (token.as_ptr() as usize).wrapping_sub(self.raw.as_ptr() as usize);
+        let column = if offset < self.raw.len() {
+            offset + 1
         } else {
-            unique_vertices.insert(v, obj.vertex.len() as u32);
-            obj.indice.push(obj.vertex.len() as u32);
-            obj.vertex.push(v);
+            1
+        };
+
+        ObjError::new(kind, self.number, column, token)
+    }
+
+    fn parse<T: FromStr>(&self, token: &str) -> Result<T, ObjError> {
+        token
+            .parse()
+            .map_err(|_| self.error(ObjErrorKind::InvalidNumber, token))
+    }
+}
+
+// returns the vertex, the index of its position and whether it has a normal
+fn parse_face_el(
+    face: &str,
+    line: &Line,
+    lists: &VertexLists,
+) -> Result<(Vertexxx, usize, bool), ObjError> {
+    let mut el = face.split('/');
+    let position = convert_index(el.next().unwrap_or(""), lists.v.len(), line)?;
+    let texture = el.next();
+    let normal = el.next();
+    if el.next().is_some() {
+        return Err(line.error(ObjErrorKind::MalformedFace, face));
+    }
+
+    let texture = match texture {
+        // v//vn has no texture
+        Some("") if normal.is_some() => None,
+        Some(i) => Some(convert_index(i, lists.vt.len(), line)?),
+        None => None,
+    };
+    let normal = match normal {
+        Some(i) => Some(convert_index(i, lists.vn.len(), line)?),
+        None => None,
+    };
+
+    Ok(lists.face_vertex(position, texture, normal))
+}
+
+// converts a 1-based (or negative, relative to the end) obj index to an index in a list of
+// `size` elements whose first one is a placeholder
+fn convert_index(i: &str, size: usize, line: &Line) -> Result<usize, ObjError> {
+    if i.is_empty() {
+        return Err(line.error(ObjErrorKind::MalformedFace, i));
+    }
+    let signed: i64 = line.parse(i)?;
+    resolve_index(signed, size).ok_or_else(|| line.error(ObjErrorKind::IndexOutOfRange, i))
+}
+
+pub(super) fn resolve_index(signed: i64, size: usize) -> Option<usize> {
+    let index = if signed < 0 {
+        size as i64 + signed
+    } else {
+        signed
+    };
+
+    if index < 1 || index >= size as i64 {
+        return None;
+    }
+    Some(index as usize)
+}
+
+// the default hasher is made to resist collision attacks, which is not an issue here and
+// makes it much slower on small keys like the vertices
+#[derive(Default)]
+struct VertexHasher(u64);
+
+impl Hasher for VertexHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u32(byte as u32);
         }
     }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0 = (self.0.rotate_left(5) ^ i as u64).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+type VertexMap = HashMap<Vertexxx, u32, BuildHasherDefault<VertexHasher>>;
+
+fn handle_face(triangle: [Vertexxx; 3], obj: &mut Object, unique_vertices: &mut VertexMap) {
+    for v in triangle {
+        let index = *unique_vertices.entry(v).or_insert_with(|| {
+            obj.vertex.push(v);
+            obj.vertex.len() as u32 - 1
+        });
+        obj.indice.push(index);
+    }
 }
 
-fn calculate_normal(v1: &Vertexxx, v2: &Vertexxx, v3: &Vertexxx) -> [f32; 3] {
-    let v1 = Vec3::from(&v1.position);
-    let v2 = Vec3::from(&v2.position);
-    let v3 = Vec3::from(&v3.position);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Vec3::normalize(&Vec3::cross(&(v2 - v1), &(v3 - v2))).to_array()
+    fn parse(obj: &str, options: &ParseOptions) -> Result<(Object, Vec<ParseWarning>), ObjError> {
+        Object::parse_reader(obj.as_bytes(), options, |_| {})
+    }
+
+    #[test]
+    fn nan_group() {
+        // the faces of the second group only use positions that are not numbers
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv nan nan nan\nv nan nan nan\nv nan 1 nan\n\
+                   g numbers\nf 1 2 3\ng nans\nf 4 5 6\ng empty\n";
+        let (object, _) = parse(obj, &ParseOptions::default()).unwrap();
+        let names: Vec<&str> = object.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["numbers", "nans"]);
+        assert_eq!(object.submeshes.len(), 2);
+        assert_eq!(object.submeshes[1].group, 1);
+        let nans = &object.groups[1];
+        assert_eq!(nans.min.to_array(), [0.0, 1.0, 0.0]);
+        assert_eq!(nans.max.to_array(), [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn smoothing_on() {
+        // two faces folded along their shared edge, smoothed by s on as by s 1
+        let faces = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 1\nf 1 2 3\nf 3 2 4\n";
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let normals = |smoothing: &str| {
+            let (object, warnings) = parse(&format!("{smoothing}\n{faces}"), &options).unwrap();
+            assert!(warnings.is_empty());
+            object
+                .indice
+                .iter()
+                .map(|&i| object.vertex[i as usize].normal)
+                .collect::<Vec<_>>()
+        };
+        let smoothed = normals("s on");
+        assert_eq!(smoothed, normals("s 1"));
+        // the corners of the shared edge have the same normal in both faces
+        assert_eq!(smoothed[1], smoothed[4]);
+        assert_ne!(smoothed, normals("s off"));
+
+        let error = parse(&format!("s maybe\n{faces}"), &options).unwrap_err();
+        assert_eq!(error.kind, ObjErrorKind::InvalidNumber);
+    }
+
+    #[test]
+    fn mixed_normals() {
+        // the first vertex has a normal pointing away from the face, the others have none
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 1 0 0\nf 1//1 2 3\n";
+        let (object, warnings) = parse(obj, &ParseOptions::default()).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, ObjErrorKind::MalformedFace);
+        assert_eq!((warnings[0].line, warnings[0].column), (5, 8));
+        let normals: Vec<[f32; 3]> = object
+            .indice
+            .iter()
+            .map(|&i| object.vertex[i as usize].normal)
+            .collect();
+        assert_eq!(normals, [[0.0, 0.0, 1.0]; 3]);
+
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let error = parse(obj, &options).unwrap_err();
+        assert_eq!(error.kind, ObjErrorKind::MalformedFace);
+        assert_eq!(error.token, "2");
+        // all of the vertices with a normal
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 1 0 0\nf 1//1 2//1 3//1\n";
+        let (object, _) = parse(obj, &options).unwrap();
+        assert_eq!(
+            object.vertex[object.indice[2] as usize].normal,
+            [1.0, 0.0, 0.0]
+        );
+    }
 }
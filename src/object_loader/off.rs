@@ -0,0 +1,287 @@
+use super::{
+    object::{ObjParser, ParseOptions},
+    Object, Vertexxx,
+};
+use std::error::Error;
+
+impl Object {
+    // loads an off file. The header keyword tells which values follow the position of each
+    // vertex: N for a normal, C for a color and ST for texture coordinates, as in COFF or
+    // STNOFF. Faces are polygons of any size, their optional colors are ignored. A file
+    // without faces is loaded as a point cloud
+    pub fn parse_off(data: &[u8], options: &ParseOptions) -> Result<Self, Box<dyn Error>> {
+        let file = std::str::from_utf8(data)?;
+        // without comments and blank lines
+        let mut lines = file
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (_, header) = lines.next().ok_or("empty off file")?;
+        let mut header = header.split_ascii_whitespace();
+        let keyword = header.next().unwrap_or("");
+        let layout = Layout::new(keyword).ok_or(format!("unsupported off keyword {keyword}"))?;
+
+        // the counts may follow the keyword on the same line
+        let mut counts: Vec<&str> = header.collect();
+        if counts.is_empty() {
+            let (_, line) = lines.next().ok_or("missing vertex and face counts")?;
+            counts = line.split_ascii_whitespace().collect();
+        }
+        let (vertex_count, face_count) = match counts[..] {
+            [vertices, faces, ..] => (vertices.parse::<usize>()?, faces.parse::<usize>()?),
+            _ => return Err("missing vertex and face counts".into()),
+        };
+
+        let mut vertices = Vec::with_capacity(vertex_count.min(data.len()));
+        let mut values = Vec::new();
+        for _ in 0..vertex_count {
+            let (line_number, line) = lines.next().ok_or("missing vertices")?;
+            values.clear();
+            for token in line.split_ascii_whitespace() {
+                values.push(
+                    token
+                        .parse::<f32>()
+                        .map_err(|e| format!("line {line_number}: {e}"))?,
+                );
+            }
+            if values.len() < layout.len {
+                return Err(format!("line {line_number}: expected {} values", layout.len).into());
+            }
+            let v = &values;
+            vertices.push(Vertexxx {
+                position: [v[0], v[1], v[2]],
+                normal: layout
+                    .normal
+                    .map_or([0.0; 3], |n| [v[n], v[n + 1], v[n + 2]]),
+                color: layout
+                    .color
+                    .map_or([1.0; 3], |c| [v[c], v[c + 1], v[c + 2]]),
+                // same convention as the obj texture coordinates
+                texture: match v[..] {
+                    [.., s, t] if layout.has_texture => [s, 1.0 - t],
+                    _ => [0.0; 2],
+                },
+            });
+        }
+
+        // colors are either integers up to 255 or floats up to 1
+        if vertices.iter().any(|v| v.color.iter().any(|&c| c > 1.0)) {
+            for vertex in vertices.iter_mut() {
+                vertex.color = vertex.color.map(|c| c / 255.0);
+            }
+        }
+        if face_count == 0 {
+            return Ok(Self::from_points(vertices));
+        }
+
+        let has_normals = layout.normal.is_some();
+        let has_texture = layout.has_texture;
+        let mut parser = ObjParser::new(options);
+        for vertex in vertices.iter() {
+            parser.lists.v.push([vertex.position, vertex.color]);
+            if has_normals {
+                parser.lists.vn.push(vertex.normal);
+            }
+            if has_texture {
+                parser.lists.vt.push(vertex.texture);
+            }
+        }
+
+        for _ in 0..face_count {
+            let (line_number, line) = lines.next().ok_or("missing faces")?;
+            let error = |reason: &str| format!("line {line_number}: {reason}");
+            let mut tokens = line.split_ascii_whitespace().map(str::parse::<usize>);
+            let size = tokens
+                .next()
+                .and_then(Result::ok)
+                .ok_or_else(|| error("invalid face"))?;
+
+            parser.polygon.clear();
+            for _ in 0..size {
+                let index = tokens
+                    .next()
+                    .and_then(Result::ok)
+                    .ok_or_else(|| error("invalid face"))?;
+                if index >= vertices.len() {
+                    return Err(error(&format!("index {index} out of range")).into());
+                }
+                // the lists start with a placeholder
+                let i = index + 1;
+                let vertex =
+                    parser
+                        .lists
+                        .face_vertex(i, has_texture.then_some(i), has_normals.then_some(i));
+                parser.polygon.push(vertex);
+            }
+            // the edges and points some files have among their faces
+            if size >= 3 {
+                parser.push_polygon();
+            }
+        }
+
+        if parser.triangles.is_empty() {
+            return Err("the off file has no triangles".into());
+        }
+
+        let (obj, _) = parser.finish();
+        Ok(obj)
+    }
+}
+
+// where the values of a vertex line are, the texture coordinates are always the last two
+struct Layout {
+    normal: Option<usize>,
+    color: Option<usize>,
+    has_texture: bool,
+    len: usize,
+}
+
+impl Layout {
+    // the prefixes always come in the order ST, C, N, while the values are in the order
+    // normal, color, texture coordinates
+    fn new(keyword: &str) -> Option<Self> {
+        let rest = keyword.strip_suffix("OFF")?;
+        let (has_texture, rest) = match rest.strip_prefix("ST") {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (has_color, rest) = match rest.strip_prefix('C') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if !matches!(rest, "" | "N") {
+            return None;
+        }
+
+        let mut layout = Self {
+            normal: None,
+            color: None,
+            has_texture,
+            len: 3,
+        };
+        if rest == "N" {
+            layout.normal = Some(layout.len);
+            layout.len += 3;
+        }
+        if has_color {
+            // rgba colors have an alpha, which is ignored
+            layout.color = Some(layout.len);
+            layout.len += 3;
+        }
+        if has_texture {
+            layout.len += 2;
+        }
+        Some(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::Topology;
+
+    fn parse(data: &str) -> Result<Object, Box<dyn Error>> {
+        Object::parse_off(data.as_bytes(), &ParseOptions::default())
+    }
+
+    // the position, normal, color and texture coordinates of every corner
+    fn corners(object: &Object) -> Vec<Vertexxx> {
+        object
+            .indice
+            .iter()
+            .map(|&i| object.vertex[i as usize])
+            .collect()
+    }
+
+    #[test]
+    fn plain() {
+        // the counts on the keyword line, a quad and an edge which is skipped
+        let data = "OFF 4 2 0\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n2 0 2\n";
+        let object = parse(data).unwrap();
+        assert_eq!(object.topology, Topology::Triangles);
+        assert_eq!(object.indice.len(), 6);
+        for vertex in corners(&object) {
+            // the normals are generated
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(vertex.color, [1.0; 3]);
+        }
+        assert_eq!(object.size.to_array(), [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn colors_and_normals() {
+        let data = "CNOFF
+# vertices, faces and edges
+3 1 0
+
+0 0 0  0 0 -1  255 0 0 255
+1 0 0  0 0 -1  0 255 0 255 # rgba
+0 1 0  0 0 -1  0 0 255 255
+3 0 1 2  255 255 255
+";
+        let vertices = corners(&parse(data).unwrap());
+        let expected = [
+            ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]),
+            ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ];
+        assert_eq!(vertices.len(), 3);
+        for (vertex, (position, color)) in vertices.iter().zip(expected) {
+            assert_eq!(vertex.position, position);
+            // the normals of the file are kept, even against the winding
+            assert_eq!(vertex.normal, [0.0, 0.0, -1.0]);
+            assert_eq!(vertex.color, color);
+        }
+
+        // float colors are already between 0 and 1
+        let data = "COFF\n3 1 0\n0 0 0 0.5 0 0\n1 0 0 0 0.5 0\n0 1 0 0 0 0.5\n3 0 1 2\n";
+        let colors: Vec<[f32; 3]> = corners(&parse(data).unwrap())
+            .iter()
+            .map(|vertex| vertex.color)
+            .collect();
+        assert_eq!(colors, [[0.5, 0.0, 0.0], [0.0, 0.5, 0.0], [0.0, 0.0, 0.5]]);
+    }
+
+    #[test]
+    fn texture_coordinates() {
+        let data = "STCOFF\n3 1 0\n0 0 0 1 1 1 0 0.25\n1 0 0 1 1 1 1 0.25\n\
+                    0 1 0 1 1 1 0 1\n3 0 1 2\n";
+        let textures: Vec<[f32; 2]> = corners(&parse(data).unwrap())
+            .iter()
+            .map(|vertex| vertex.texture)
+            .collect();
+        assert_eq!(textures, [[0.0, 0.75], [1.0, 0.75], [0.0, 0.0]]);
+    }
+
+    #[test]
+    fn points() {
+        let object = parse("COFF\n2 0 0\n0 0 0 255 0 0\n1 2 3 0 0 255\n").unwrap();
+        assert_eq!(object.topology, Topology::Points);
+        assert_eq!(object.vertex.len(), 2);
+        assert_eq!(object.vertex[1].color, [0.0, 0.0, 1.0]);
+        assert_eq!(object.size.to_array(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn invalid() {
+        for data in [
+            "",
+            "PLY\n3 1 0\n",
+            "NCOFF\n3 1 0\n",
+            "OFF\n",
+            "OFF\nthree 1 0\n",
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n",
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1\n3 0 1 2\n",
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 zero\n3 0 1 2\n",
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n",
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1\n",
+            "OFF\n3 2 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n",
+            // only edges
+            "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n2 0 1\n",
+        ] {
+            assert!(parse(data).is_err(), "{data}");
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use super::{
+    error::{ObjError, ParseWarning},
+    object::{resolve_index, tokenize, trim_line_end, Line, ObjParser, ParseOptions, VertexLists},
+    Object,
+};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+// number of bytes parsed by a thread between two updates of the progress
+const PROGRESS_STEP: usize = 1 << 20;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+// a statement of a chunk, replayed in the order of the file once every chunk is parsed
+// `counts` are the numbers of v, vt and vn elements of the chunk before the statement
+enum Record<'a> {
+    // a well formed face, its indices are resolved during the merge
+    Face {
+        raw: &'a [u8],
+        number: usize,
+        elements: Range<usize>,
+        counts: [usize; 3],
+    },
+    // any other statement, given to the sequential parser during the merge
+    Line {
+        raw: &'a [u8],
+        number: usize,
+        counts: [usize; 3],
+    },
+    // an invalid v, vt or vn statement, its placeholder is already in the lists
+    Error(ObjError),
+}
+
+// the result of the parsing of a part of the file, line numbers are relative to its start
+#[derive(Default)]
+struct Chunk<'a> {
+    lists: VertexLists,
+    // v, vt and vn indices of the face elements as written in the file, 0 when absent
+    elements: Vec<[i64; 3]>,
+    records: Vec<Record<'a>>,
+    lines: usize,
+}
+
+impl Object {
+    // parses the object with `threads` threads, the result is the same as the one of
+    // `parse_reader`. The v, vt, vn and f statements are parsed in parallel, then the
+    // faces are assembled in the order of the file
+    // `progress` is regularly called with the number of bytes parsed so far
+    pub fn parse_parallel(
+        object: &[u8],
+        options: &ParseOptions,
+        threads: usize,
+        mut progress: impl FnMut(u64),
+    ) -> Result<(Self, Vec<ParseWarning>), ObjError> {
+        let consumed = AtomicU64::new(0);
+        let finished = AtomicUsize::new(0);
+        let caller = thread::current();
+        let chunks: Vec<Chunk> = thread::scope(|scope| {
+            let handles: Vec<_> = split_chunks(object, threads.max(1))
+                .into_iter()
+                .map(|data| {
+                    let caller = caller.clone();
+                    let (consumed, finished) = (&consumed, &finished);
+                    scope.spawn(move || {
+                        let chunk = parse_chunk(data, consumed);
+                        finished.fetch_add(1, Ordering::Release);
+                        caller.unpark();
+                        chunk
+                    })
+                })
+                .collect();
+            while finished.load(Ordering::Acquire) < handles.len() {
+                progress(consumed.load(Ordering::Relaxed));
+                thread::park_timeout(PROGRESS_INTERVAL);
+            }
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        progress(object.len() as u64);
+
+        let mut parser = ObjParser::new(options);
+        let mut line_offset = 0;
+        for chunk in chunks.iter() {
+            let start = parser.lists.counts();
+            for record in chunk.records.iter() {
+                match record {
+                    Record::Face {
+                        raw,
+                        number,
+                        elements,
+                        counts,
+                    } => {
+                        parser.extend_lists(&chunk.lists, start, *counts);
+                        if !parser.push_raw_face(&chunk.elements[elements.clone()]) {
+                            // an index is out of range, the sequential parser reports it
+                            let raw = String::from_utf8_lossy(raw);
+                            parser.parse_line(&raw, number + line_offset)?;
+                        }
+                    }
+                    Record::Line {
+                        raw,
+                        number,
+                        counts,
+                    } => {
+                        parser.extend_lists(&chunk.lists, start, *counts);
+                        let raw = String::from_utf8_lossy(raw);
+                        parser.parse_line(&raw, number + line_offset)?;
+                    }
+                    Record::Error(e) => {
+                        let mut e = e.clone();
+                        e.line += line_offset;
+                        parser.report(Err(e))?;
+                    }
+                }
+            }
+            parser.extend_lists(&chunk.lists, start, chunk.lists.counts());
+            line_offset += chunk.lines;
+        }
+
+        Ok(parser.finish())
+    }
+}
+
+impl ObjParser<'_> {
+    // adds the elements of the chunk up to `counts`, `start` are the sizes of the lists
+    // before the chunk
+    fn extend_lists(&mut self, chunk: &VertexLists, start: [usize; 3], counts: [usize; 3]) {
+        let lists = &mut self.lists;
+        lists
+            .v
+            .extend_from_slice(&chunk.v[lists.v.len() - start[0]..counts[0]]);
+        lists
+            .vt
+            .extend_from_slice(&chunk.vt[lists.vt.len() - start[1]..counts[1]]);
+        lists
+            .vn
+            .extend_from_slice(&chunk.vn[lists.vn.len() - start[2]..counts[2]]);
+    }
+
+    // returns false when an index is out of range
+    fn push_raw_face(&mut self, elements: &[[i64; 3]]) -> bool {
+        let [v, vt, vn] = self.lists.counts();
+        self.polygon.clear();
+        for &[position, texture, normal] in elements {
+            let Some(position) = resolve_index(position, v) else {
+                return false;
+            };
+            let texture = match texture {
+                0 => None,
+                i => match resolve_index(i, vt) {
+                    Some(i) => Some(i),
+                    None => return false,
+                },
+            };
+            let normal = match normal {
+                0 => None,
+                i => match resolve_index(i, vn) {
+                    Some(i) => Some(i),
+                    None => return false,
+                },
+            };
+            let vertex = self.lists.face_vertex(position, texture, normal);
+            self.polygon.push(vertex);
+        }
+        self.push_polygon();
+        true
+    }
+}
+
+// splits the data in `count` parts of about the same size, at line boundaries
+fn split_chunks(data: &[u8], count: usize) -> Vec<&[u8]> {
+    let size = data.len() / count;
+    let mut chunks = Vec::with_capacity(count);
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = if chunks.len() == count - 1 || size >= rest.len() {
+            rest.len()
+        } else {
+            match rest[size..].iter().position(|&b| b == b'\n') {
+                Some(i) => size + i + 1,
+                None => rest.len(),
+            }
+        };
+        let (chunk, next) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = next;
+    }
+    chunks
+}
+
+fn parse_chunk<'a>(data: &'a [u8], consumed: &AtomicU64) -> Chunk<'a> {
+    let mut chunk = Chunk::default();
+    let mut tokens = Vec::new();
+    let mut not_reported = 0;
+
+    for raw in data.split_inclusive(|&b| b == b'\n') {
+        chunk.lines += 1;
+        not_reported += raw.len();
+        if not_reported >= PROGRESS_STEP {
+            consumed.fetch_add(not_reported as u64, Ordering::Relaxed);
+            not_reported = 0;
+        }
+
+        let raw = trim_line_end(raw);
+        let text = String::from_utf8_lossy(raw);
+        tokenize(text.as_bytes(), &mut tokens);
+        let line = Line {
+            raw: &text,
+            number: chunk.lines,
+            tokens: &tokens,
+        };
+        if line.len() < 2 {
+            continue;
+        }
+
+        let counts = chunk.lists.counts();
+        let record = match line.token(0) {
+            "v" | "vt" | "vn" => match chunk.lists.parse(&line) {
+                Ok(()) => continue,
+                Err(e) => Record::Error(e),
+            },
+            "f" => match parse_raw_face(&line, &mut chunk.elements) {
+                Some(elements) => Record::Face {
+                    raw,
+                    number: line.number,
+                    elements,
+                    counts,
+                },
+                None => Record::Line {
+                    raw,
+                    number: line.number,
+                    counts,
+                },
+            },
+            _ => Record::Line {
+                raw,
+                number: line.number,
+                counts,
+            },
+        };
+        chunk.records.push(record);
+    }
+    consumed.fetch_add(not_reported as u64, Ordering::Relaxed);
+
+    chunk
+}
+
+// parses the indices of the face elements, faces with a problem are left to the sequential
+// parser so that it is reported the same way
+fn parse_raw_face(line: &Line, elements: &mut Vec<[i64; 3]>) -> Option<Range<usize>> {
+    if line.len() < 4 {
+        return None;
+    }
+    let start = elements.len();
+    for el in line.tokens_from(1) {
+        match parse_raw_element(el) {
+            Some(element) => elements.push(element),
+            None => {
+                elements.truncate(start);
+                return None;
+            }
+        }
+    }
+    // the elements with and without a normal are reported when they are mixed
+    let has_normal = elements[start][2] != 0;
+    if elements[start..]
+        .iter()
+        .any(|el| (el[2] != 0) != has_normal)
+    {
+        elements.truncate(start);
+        return None;
+    }
+    Some(start..elements.len())
+}
+
+fn parse_raw_element(el: &str) -> Option<[i64; 3]> {
+    let mut el = el.split('/');
+    let position = parse_raw_index(el.next()?)?;
+    let texture = el.next();
+    let normal = el.next();
+    if el.next().is_some() {
+        return None;
+    }
+
+    let normal = match normal {
+        Some(i) => parse_raw_index(i)?,
+        None => 0,
+    };
+    let texture = match texture {
+        // v//vn has no texture
+        Some("") if normal != 0 => 0,
+        Some(i) => parse_raw_index(i)?,
+        None => 0,
+    };
+    Some([position, texture, normal])
+}
+
+// 0 is never a valid index, it is used for the absent ones
+fn parse_raw_index(i: &str) -> Option<i64> {
+    i.parse().ok().filter(|&i| i != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::Topology;
+
+    // a cube made of quads, whose faces are in a group far from its vertices and refer to
+    // them with both absolute and negative indices
+    fn cube() -> String {
+        let mut obj = String::from("# cube\nmtllib cube.mtl\n");
+        for i in 0..8 {
+            let (x, y, z) = (i & 1, (i >> 1) & 1, (i >> 2) & 1);
+            obj += &format!("v {x} {y} {z}\nvt {x} {y}\n");
+        }
+        for normal in ["1 0 0", "-1 0 0", "0 1 0", "0 -1 0", "0 0 1", "0 0 -1"] {
+            obj += &format!("vn {normal}\n");
+        }
+        // enough lines for each thread to have a part of the vertices and of the faces
+        for i in 0..40 {
+            obj += &format!("# padding {i}\n");
+        }
+        obj += "o cube\ng sides\nusemtl red\ns 1\n";
+        obj += "f 2/2/1 4/4/1 8/8/1 6/6/1\n";
+        obj += "f -8/-8/-5 -3/-3/-5 -1/-1/-5 -6/-6/-5\n";
+        obj += "f 1 2 6 5\nf -6//-4 -5//-4 -1//-4 -2//-4\n";
+        obj += "g caps\nusemtl blue\ns off\n";
+        // a vertex added after the faces and a negative index referring to it
+        obj += "v 0.5 0.5 1.5\nf 5 6 8 7\nf 5/5 6/6 -1\nf 1 3 4 2\n";
+        // warnings of the lenient mode
+        obj += "vp 0.5\nf 1 2\nf 1 2 99\nusemtl\nv 1 2\nf 1/1/1 2/2/2 3/3/3\n";
+        // a face whose first element only has a normal
+        obj += "f 1//1 2 3\n";
+        obj
+    }
+
+    fn assert_same(sequential: &Object, parallel: &Object) {
+        assert_eq!(sequential.topology, Topology::Triangles);
+        assert_eq!(sequential.topology, parallel.topology);
+        assert_eq!(sequential.vertex, parallel.vertex);
+        assert_eq!(sequential.indice, parallel.indice);
+        assert_eq!(sequential.mtllibs, parallel.mtllibs);
+        let materials = |object: &Object| -> Vec<String> {
+            object.materials.iter().map(|m| m.name.clone()).collect()
+        };
+        assert_eq!(materials(sequential), materials(parallel));
+        let groups = |object: &Object| -> Vec<(String, [f32; 3], [f32; 3])> {
+            object
+                .groups
+                .iter()
+                .map(|g| (g.name.clone(), g.min.to_array(), g.max.to_array()))
+                .collect()
+        };
+        assert_eq!(groups(sequential), groups(parallel));
+        let submeshes = |object: &Object| -> Vec<(Option<usize>, usize, u32, u32)> {
+            object
+                .submeshes
+                .iter()
+                .map(|s| (s.material, s.group, s.start, s.count))
+                .collect()
+        };
+        assert_eq!(submeshes(sequential), submeshes(parallel));
+        assert_eq!(sequential.size.to_array(), parallel.size.to_array());
+        assert_eq!(sequential.center.to_array(), parallel.center.to_array());
+    }
+
+    #[test]
+    fn same_as_sequential() {
+        let obj = cube();
+        for crease_angle in [None, Some(1.0)] {
+            let options = ParseOptions {
+                crease_angle,
+                ..Default::default()
+            };
+            let (sequential, warnings) =
+                Object::parse_reader(obj.as_bytes(), &options, |_| {}).unwrap();
+            assert!(!sequential.indice.is_empty() && !warnings.is_empty());
+            // the empty cube object is dropped for its sides group
+            assert_eq!(sequential.groups.len(), 2);
+            for threads in 1..=8 {
+                // the padding keeps the faces out of the chunks of the vertices
+                assert_eq!(split_chunks(obj.as_bytes(), threads).len(), threads);
+                let (parallel, parallel_warnings) =
+                    Object::parse_parallel(obj.as_bytes(), &options, threads, |_| {}).unwrap();
+                assert_same(&sequential, &parallel);
+                assert_eq!(
+                    format!("{warnings:?}"),
+                    format!("{parallel_warnings:?}"),
+                    "{threads} threads"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_error_as_sequential() {
+        let obj = cube() + "f 1 2 -100\n";
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let sequential = Object::parse_reader(obj.as_bytes(), &options, |_| {}).unwrap_err();
+        for threads in 1..=8 {
+            let parallel =
+                Object::parse_parallel(obj.as_bytes(), &options, threads, |_| {}).unwrap_err();
+            assert_eq!(format!("{sequential:?}"), format!("{parallel:?}"));
+        }
+    }
+}
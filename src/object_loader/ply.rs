@@ -0,0 +1,594 @@
+use super::{
+    object::{ObjParser, ParseOptions},
+    Object, Vertexxx,
+};
+use std::{error::Error, str::SplitAsciiWhitespace};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        kind: Scalar,
+    },
+    List {
+        name: String,
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Object {
+    // loads a ply file in any of its three formats. The vertices may have normals (nx, ny,
+    // nz), colors (red, green, blue, as integers or floats) and texture coordinates (u, v or
+    // s, t), the faces are polygons of any size. A file without faces is a point cloud
+    pub fn parse_ply(data: &[u8], options: &ParseOptions) -> Result<Self, Box<dyn Error>> {
+        let (format, elements, body) = parse_header(data)?;
+        let mut body = Body::new(format, &data[body..])?;
+
+        let mut vertices: Vec<[f32; 11]> = Vec::new();
+        let (mut has_normals, mut has_colors, mut has_uvs) = (false, false, false);
+        let mut indices = Vec::new();
+        let mut faces = Vec::new();
+        for element in elements.iter() {
+            match element.name.as_str() {
+                "vertex" => {
+                    let layout = VertexLayout::new(element)?;
+                    has_normals = layout.normal.is_some();
+                    has_colors = layout.color.is_some();
+                    has_uvs = layout.texture.is_some();
+                    let mut values = vec![0.0; layout.kinds.len()];
+                    // the count is only trusted as far as there are bytes left for it
+                    vertices.reserve(element.count.min(body.len()));
+                    for _ in 0..element.count {
+                        vertices.push(layout.read(&mut body, &mut values)?);
+                    }
+                }
+                "face" => {
+                    let list = element
+                        .properties
+                        .iter()
+                        .position(|p| {
+                            matches!(p, Property::List { name, .. }
+                                if name == "vertex_indices" || name == "vertex_index")
+                        })
+                        .ok_or("face element without vertex_indices")?;
+                    for _ in 0..element.count {
+                        for (i, property) in element.properties.iter().enumerate() {
+                            if i != list {
+                                body.skip(property)?;
+                                continue;
+                            }
+                            let Property::List { count, item, .. } = property else {
+                                unreachable!()
+                            };
+                            let start = indices.len();
+                            for _ in 0..body.read(*count)? as usize {
+                                indices.push(body.read(*item)?);
+                            }
+                            faces.push(start..indices.len());
+                        }
+                    }
+                }
+                _ => {
+                    for _ in 0..element.count {
+                        for property in element.properties.iter() {
+                            body.skip(property)?;
+                        }
+                    }
+                }
+            }
+        }
+        // scanners often write points only
+        if faces.is_empty() {
+            let points = vertices
+                .iter()
+                .map(|v| Vertexxx {
+                    position: [v[0], v[1], v[2]],
+                    normal: [v[3], v[4], v[5]],
+                    color: if has_colors {
+                        [v[6], v[7], v[8]]
+                    } else {
+                        [1.0; 3]
+                    },
+                    texture: if has_uvs {
+                        [v[9], 1.0 - v[10]]
+                    } else {
+                        [0.0; 2]
+                    },
+                })
+                .collect();
+            return Ok(Self::from_points(points));
+        }
+
+        let mut parser = ObjParser::new(options);
+        for vertex in vertices.iter() {
+            let color = if has_colors {
+                [vertex[6], vertex[7], vertex[8]]
+            } else {
+                [1.0; 3]
+            };
+            parser
+                .lists
+                .v
+                .push([[vertex[0], vertex[1], vertex[2]], color]);
+            if has_normals {
+                parser.lists.vn.push([vertex[3], vertex[4], vertex[5]]);
+            }
+            if has_uvs {
+                // same convention as the obj texture coordinates
+                parser.lists.vt.push([vertex[9], 1.0 - vertex[10]]);
+            }
+        }
+
+        for face in faces {
+            if face.len() < 3 {
+                continue;
+            }
+            parser.polygon.clear();
+            for &index in indices[face].iter() {
+                if index < 0.0 || index >= vertices.len() as f64 {
+                    return Err(format!("face index {index} out of range").into());
+                }
+                // the lists start with a placeholder
+                let i = index as usize + 1;
+                let vertex =
+                    parser
+                        .lists
+                        .face_vertex(i, has_uvs.then_some(i), has_normals.then_some(i));
+                parser.polygon.push(vertex);
+            }
+            parser.push_polygon();
+        }
+
+        let (obj, _) = parser.finish();
+        Ok(obj)
+    }
+}
+
+// returns the format, the elements and the offset of the body
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), Box<dyn Error>> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+
+    for (line_number, raw) in data.split_inclusive(|&b| b == b'\n').enumerate() {
+        offset += raw.len();
+        let line_number = line_number + 1;
+        let line: Vec<&str> = std::str::from_utf8(raw)?.split_ascii_whitespace().collect();
+
+        if line_number == 1 {
+            if line != ["ply"] {
+                return Err("not a ply file".into());
+            }
+            continue;
+        }
+        match line.as_slice() {
+            ["format", kind, _version] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("line {line_number}: unknown format {kind}").into()),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or(format!("line {line_number}: property before any element"))?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count: parse_scalar(count, line_number)?,
+                    item: parse_scalar(item, line_number)?,
+                }),
+            ["property", kind, name] => elements
+                .last_mut()
+                .ok_or(format!("line {line_number}: property before any element"))?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    kind: parse_scalar(kind, line_number)?,
+                }),
+            ["end_header"] => {
+                let format = format.ok_or("missing format in the ply header")?;
+                return Ok((format, elements, offset));
+            }
+            ["comment" | "obj_info", ..] | [] => {}
+            _ => return Err(format!("line {line_number}: invalid ply header line").into()),
+        }
+    }
+
+    Err("missing end_header in the ply header".into())
+}
+
+fn parse_scalar(kind: &str, line_number: usize) -> Result<Scalar, Box<dyn Error>> {
+    Ok(match kind {
+        "char" | "int8" => Scalar::I8,
+        "uchar" | "uint8" => Scalar::U8,
+        "short" | "int16" => Scalar::I16,
+        "ushort" | "uint16" => Scalar::U16,
+        "int" | "int32" => Scalar::I32,
+        "uint" | "uint32" => Scalar::U32,
+        "float" | "float32" => Scalar::F32,
+        "double" | "float64" => Scalar::F64,
+        _ => return Err(format!("line {line_number}: unknown type {kind}").into()),
+    })
+}
+
+impl Scalar {
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    // integer colors go from 0 to the maximum of their type
+    fn color_scale(self) -> f32 {
+        match self {
+            Self::I8 => i8::MAX as f32,
+            Self::U8 => u8::MAX as f32,
+            Self::I16 => i16::MAX as f32,
+            Self::U16 => u16::MAX as f32,
+            Self::I32 => i32::MAX as f32,
+            Self::U32 => u32::MAX as f32,
+            Self::F32 | Self::F64 => 1.0,
+        }
+    }
+}
+
+// where the values of a vertex are in its properties
+struct VertexLayout {
+    kinds: Vec<Scalar>,
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    color: Option<([usize; 3], [f32; 3])>,
+    texture: Option<[usize; 2]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Result<Self, Box<dyn Error>> {
+        let kinds = element
+            .properties
+            .iter()
+            .map(|p| match p {
+                Property::Scalar { kind, .. } => Ok(*kind),
+                Property::List { .. } => Err("list properties of vertices are not supported"),
+            })
+            .collect::<Result<_, _>>()?;
+        let find = |names: &[&str]| {
+            element.properties.iter().position(
+                |p| matches!(p, Property::Scalar { name, .. } if names.contains(&name.as_str())),
+            )
+        };
+        let find_all =
+            |names: [&[&str]; 3]| Some([find(names[0])?, find(names[1])?, find(names[2])?]);
+
+        let position =
+            find_all([&["x"], &["y"], &["z"]]).ok_or("vertex element without x, y, z")?;
+        let normal = find_all([&["nx"], &["ny"], &["nz"]]);
+        let color = find_all([
+            &["red", "r", "diffuse_red"],
+            &["green", "g", "diffuse_green"],
+            &["blue", "b", "diffuse_blue"],
+        ])
+        .map(|color| {
+            let scale = color.map(|i| match element.properties[i] {
+                Property::Scalar { kind, .. } => kind.color_scale(),
+                Property::List { .. } => 1.0,
+            });
+            (color, scale)
+        });
+        let texture = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]]
+            .iter()
+            .find_map(|[u, v]| Some([find(&[u])?, find(&[v])?]));
+
+        Ok(Self {
+            kinds,
+            position,
+            normal,
+            color,
+            texture,
+        })
+    }
+
+    // position, normal, color and texture coordinates, `values` holds one value per property
+    fn read(&self, body: &mut Body, values: &mut [f32]) -> Result<[f32; 11], Box<dyn Error>> {
+        for (value, kind) in values.iter_mut().zip(self.kinds.iter()) {
+            *value = body.read(*kind)? as f32;
+        }
+
+        let mut vertex = [0.0; 11];
+        for axis in 0..3 {
+            vertex[axis] = values[self.position[axis]];
+            if let Some(normal) = self.normal {
+                vertex[3 + axis] = values[normal[axis]];
+            }
+            if let Some((color, scale)) = self.color {
+                vertex[6 + axis] = values[color[axis]] / scale[axis];
+            }
+        }
+        if let Some([u, v]) = self.texture {
+            vertex[9] = values[u];
+            vertex[10] = values[v];
+        }
+        Ok(vertex)
+    }
+}
+
+// the data after the header, read value by value
+struct Body<'a> {
+    format: Format,
+    data: &'a [u8],
+    tokens: Option<SplitAsciiWhitespace<'a>>,
+}
+
+impl<'a> Body<'a> {
+    fn new(format: Format, data: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        let tokens = match format {
+            Format::Ascii => Some(std::str::from_utf8(data)?.split_ascii_whitespace()),
+            _ => None,
+        };
+        Ok(Self {
+            format,
+            data,
+            tokens,
+        })
+    }
+
+    // the number of bytes left, more than the number of values left
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read(&mut self, kind: Scalar) -> Result<f64, Box<dyn Error>> {
+        if let Some(tokens) = &mut self.tokens {
+            let token = tokens.next().ok_or("unexpected end of the ply file")?;
+            return Ok(token.parse()?);
+        }
+
+        let size = kind.size();
+        if self.data.len() < size {
+            return Err("unexpected end of the ply file".into());
+        }
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+
+        Ok(match kind {
+            Scalar::I8 => i8::from_le_bytes([bytes[0]]) as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes),
+        })
+    }
+
+    fn skip(&mut self, property: &Property) -> Result<(), Box<dyn Error>> {
+        match property {
+            Property::Scalar { kind, .. } => {
+                self.read(*kind)?;
+            }
+            Property::List { count, item, .. } => {
+                for _ in 0..self.read(*count)? as usize {
+                    self.read(*item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::Topology;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 2
+property list uchar int vertex_indices
+end_header
+";
+
+    // a square of two triangles whose corners are red, green, blue and white
+    const VERTICES: [([f32; 3], [u8; 3]); 4] = [
+        ([0.0, 0.0, 0.0], [255, 0, 0]),
+        ([1.0, 0.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0, 0.0], [0, 0, 255]),
+        ([0.0, 1.0, 0.0], [255, 255, 255]),
+    ];
+    const FACES: [[i32; 3]; 2] = [[0, 1, 2], [0, 2, 3]];
+
+    fn binary(format: &str, to_bytes: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let mut data = format!("ply\nformat {format} 1.0\n{HEADER}").into_bytes();
+        for (position, color) in VERTICES {
+            for value in position {
+                data.extend(to_bytes(&value.to_le_bytes()));
+            }
+            data.extend_from_slice(&color);
+        }
+        for face in FACES {
+            data.push(3);
+            for index in face {
+                data.extend(to_bytes(&index.to_le_bytes()));
+            }
+        }
+        data
+    }
+
+    // the triangles of the square, with the color of each of their corners
+    fn assert_square(object: &Object) {
+        assert_eq!(object.topology, Topology::Triangles);
+        let corners: Vec<([f32; 3], [f32; 3])> = object
+            .indice
+            .iter()
+            .map(|&i| {
+                let vertex = &object.vertex[i as usize];
+                (vertex.position, vertex.color)
+            })
+            .collect();
+        let expected: Vec<([f32; 3], [f32; 3])> = FACES
+            .iter()
+            .flatten()
+            .map(|&i| {
+                let (position, color) = VERTICES[i as usize];
+                (position, color.map(|c| c as f32 / 255.0))
+            })
+            .collect();
+        assert_eq!(corners, expected);
+    }
+
+    #[test]
+    fn ascii() {
+        let data = format!(
+            "ply\nformat ascii 1.0\ncomment a square\n{HEADER}\
+             0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n\
+             3 0 1 2\n3 0 2 3\n"
+        );
+        assert_square(&Object::parse_ply(data.as_bytes(), &ParseOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let data = binary("binary_little_endian", |bytes| bytes.to_vec());
+        assert_square(&Object::parse_ply(&data, &ParseOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let data = binary("binary_big_endian", |bytes| {
+            bytes.iter().rev().copied().collect()
+        });
+        assert_square(&Object::parse_ply(&data, &ParseOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn property_order() {
+        // the colors first, unknown properties in between, the position backwards and the
+        // faces after an element that is skipped
+        let data = "ply
+format ascii 1.0
+element vertex 4
+property float blue
+property uchar green
+property double z
+property int confidence
+property float y
+property float red
+property float x
+element edge 1
+property int vertex1
+property int vertex2
+element face 2
+property uchar flags
+property list uchar uint vertex_index
+property float quality
+end_header
+0 0 0 7 0 1 0
+0 255 0 7 0 0 1
+1 0 0 7 1 0 1
+1 255 0 7 1 1 0
+0 1
+9 3 0 1 2 0.5
+9 3 0 2 3 0.5
+";
+        assert_square(&Object::parse_ply(data.as_bytes(), &ParseOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn same_position_other_color() {
+        // two triangles on the same corners, one red and one blue, are not merged
+        let data = "ply\nformat ascii 1.0\nelement vertex 6\nproperty float x\n\
+                    property float y\nproperty float z\nproperty uchar red\n\
+                    property uchar green\nproperty uchar blue\nelement face 2\n\
+                    property list uchar int vertex_indices\nend_header\n\
+                    0 0 0 255 0 0\n1 0 0 255 0 0\n0 1 0 255 0 0\n\
+                    0 0 0 0 0 255\n1 0 0 0 0 255\n0 1 0 0 0 255\n\
+                    3 0 1 2\n3 3 4 5\n";
+        let object = Object::parse_ply(data.as_bytes(), &ParseOptions::default()).unwrap();
+        let colors: Vec<[f32; 3]> = object
+            .indice
+            .iter()
+            .map(|&i| object.vertex[i as usize].color)
+            .collect();
+        assert_eq!(colors[..3], [[1.0, 0.0, 0.0]; 3]);
+        assert_eq!(colors[3..], [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn points() {
+        let data = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\n\
+                    property float y\nproperty float z\nend_header\n0 0 0\n1 2 3\n";
+        let object = Object::parse_ply(data.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(object.topology, Topology::Points);
+        assert_eq!(object.vertex.len(), 2);
+    }
+
+    #[test]
+    fn invalid() {
+        let invalid = [
+            // counts far beyond the size of the body are not allocated
+            "ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\n\
+             property float x\nproperty float y\nproperty float z\nend_header\n\0\0\0",
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+             property float z\nend_header\n0 0 0\n",
+            "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n0\n",
+            "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+             end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n",
+            "ply\nformat xml 1.0\nend_header\n",
+            "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n",
+            "obj\n",
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            assert!(
+                Object::parse_ply(data.as_bytes(), &ParseOptions::default()).is_err(),
+                "file {i}"
+            );
+        }
+    }
+}
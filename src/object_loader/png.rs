@@ -0,0 +1,562 @@
+use super::texture::Texture;
+use flate2::{read::ZlibDecoder, Crc};
+use std::{error::Error, io::Read};
+
+pub const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const GRAYSCALE: u8 = 0;
+const RGB: u8 = 2;
+const PALETTE: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const RGB_ALPHA: u8 = 6;
+
+// the origin and the spacing of the pixels of the 7 passes of an interlaced image
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+impl Texture {
+    // loads a png image of any color type and bit depth, interlaced or not. The 16 bits values
+    // are reduced to 8 bits, and the transparency of a tRNS chunk becomes the alpha channel
+    pub fn parse_png(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut chunks = data
+            .strip_prefix(PNG_SIGNATURE)
+            .ok_or("missing png signature")?;
+
+        let mut header = None;
+        let mut palette: &[u8] = &[];
+        let mut transparency: &[u8] = &[];
+        let mut compressed = Vec::new();
+        loop {
+            let (kind, content, rest) = next_chunk(chunks)?;
+            chunks = rest;
+            match kind {
+                b"IHDR" => header = Some(Header::parse(content)?),
+                b"PLTE" => palette = content,
+                b"tRNS" => transparency = content,
+                b"IDAT" => compressed.extend_from_slice(content),
+                b"IEND" => break,
+                // the critical chunks have an uppercase first letter
+                _ if kind[0].is_ascii_uppercase() => {
+                    let kind = String::from_utf8_lossy(kind);
+                    return Err(format!("unsupported critical chunk {kind}").into());
+                }
+                _ => {}
+            }
+        }
+        let header = header.ok_or("missing IHDR chunk")?;
+        if header.color_type == PALETTE && palette.is_empty() {
+            return Err("missing PLTE chunk".into());
+        }
+
+        let passes: Vec<(usize, usize, usize, usize)> = if header.interlaced {
+            ADAM7.to_vec()
+        } else {
+            vec![(0, 0, 1, 1)]
+        };
+        let expected: usize = passes
+            .iter()
+            .map(|&pass| {
+                let (width, height) = header.pass_size(pass);
+                if width == 0 {
+                    0
+                } else {
+                    (header.stride(width) + 1) * height
+                }
+            })
+            .sum();
+        let mut filtered = Vec::with_capacity(expected);
+        ZlibDecoder::new(&compressed[..])
+            .take(expected as u64)
+            .read_to_end(&mut filtered)?;
+        if filtered.len() < expected {
+            return Err("the image data is truncated".into());
+        }
+
+        let (width, height) = (header.width as usize, header.height as usize);
+        let mut texture = Texture {
+            width: header.width,
+            height: header.height,
+            data: vec![0; width * height * 4],
+        };
+        let pixel = Pixel {
+            header: &header,
+            palette,
+            transparency,
+        };
+        let mut filtered = &filtered[..];
+        for pass in passes {
+            let (pass_width, pass_height) = header.pass_size(pass);
+            if pass_width == 0 || pass_height == 0 {
+                continue;
+            }
+            let stride = header.stride(pass_width);
+            let mut previous = vec![0; stride];
+            for y in 0..pass_height {
+                let (row, rest) = filtered.split_at(stride + 1);
+                filtered = rest;
+                let mut row = row.to_vec();
+                unfilter(&mut row, &previous, header.filter_bytes())?;
+                let row = &row[1..];
+
+                let (x0, y0, dx, dy) = pass;
+                for x in 0..pass_width {
+                    let i = ((y0 + y * dy) * width + x0 + x * dx) * 4;
+                    texture.data[i..i + 4].copy_from_slice(&pixel.rgba(row, x)?);
+                }
+                previous.copy_from_slice(row);
+            }
+        }
+        Ok(texture)
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(content: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if content.len() != 13 {
+            return Err("invalid IHDR chunk".into());
+        }
+        let header = Self {
+            width: u32::from_be_bytes(content[0..4].try_into().unwrap()),
+            height: u32::from_be_bytes(content[4..8].try_into().unwrap()),
+            bit_depth: content[8],
+            color_type: content[9],
+            interlaced: content[12] == 1,
+        };
+        let depths: &[u8] = match header.color_type {
+            GRAYSCALE => &[1, 2, 4, 8, 16],
+            PALETTE => &[1, 2, 4, 8],
+            RGB | GRAYSCALE_ALPHA | RGB_ALPHA => &[8, 16],
+            _ => return Err(format!("invalid color type {}", header.color_type).into()),
+        };
+        if !depths.contains(&header.bit_depth) {
+            return Err(format!(
+                "invalid bit depth {} for the color type {}",
+                header.bit_depth, header.color_type
+            )
+            .into());
+        }
+        if content[10] != 0 || content[11] != 0 || content[12] > 1 {
+            return Err("unsupported compression, filter or interlace method".into());
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err(format!("the image is {}x{}", header.width, header.height).into());
+        }
+        // the pixels are all allocated before they are decompressed
+        if header.width as u64 * header.height as u64 > 1 << 28 {
+            return Err("the image is too big".into());
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            RGB => 3,
+            GRAYSCALE_ALPHA => 2,
+            RGB_ALPHA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    // the number of bytes between a byte and the one it is filtered with
+    fn filter_bytes(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    // the length of a row of `width` pixels, without its filter type
+    fn stride(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    fn pass_size(&self, (x0, y0, dx, dy): (usize, usize, usize, usize)) -> (usize, usize) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        (
+            width.saturating_sub(x0).div_ceil(dx),
+            height.saturating_sub(y0).div_ceil(dy),
+        )
+    }
+}
+
+// the type, the content and what follows a chunk, whose crc is checked
+fn next_chunk(data: &[u8]) -> Result<(&[u8], &[u8], &[u8]), Box<dyn Error>> {
+    if data.len() < 12 {
+        return Err("the file is truncated".into());
+    }
+    let len = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+    if data.len() - 12 < len {
+        return Err("the file is truncated".into());
+    }
+    let (kind, content) = (&data[4..8], &data[8..8 + len]);
+    let crc = u32::from_be_bytes(data[8 + len..12 + len].try_into().unwrap());
+
+    let mut expected = Crc::new();
+    expected.update(&data[4..8 + len]);
+    if crc != expected.sum() {
+        let kind = String::from_utf8_lossy(kind);
+        return Err(format!("invalid crc of the {kind} chunk").into());
+    }
+    Ok((kind, content, &data[12 + len..]))
+}
+
+// reverts the filter of a row, which starts with its filter type, using the row above
+fn unfilter(row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), Box<dyn Error>> {
+    let filter = row[0];
+    let row = &mut row[1..];
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("invalid filter type {filter}").into()),
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// converts the pixels of the rows to rgba
+struct Pixel<'a> {
+    header: &'a Header,
+    palette: &'a [u8],
+    transparency: &'a [u8],
+}
+
+impl Pixel<'_> {
+    fn rgba(&self, row: &[u8], x: usize) -> Result<[u8; 4], Box<dyn Error>> {
+        let channels = self.header.channels();
+        let sample = |c| sample(row, x * channels + c, self.header.bit_depth);
+        let to_u8 = |value| to_u8(value, self.header.bit_depth);
+        // the transparent color of the images without alpha, with the samples of the image
+        let transparent = |samples: &[u16]| {
+            samples.len() * 2 == self.transparency.len()
+                && samples.iter().enumerate().all(|(i, &value)| {
+                    let transparent = &self.transparency[i * 2..i * 2 + 2];
+                    u16::from_be_bytes([transparent[0], transparent[1]]) == value
+                })
+        };
+
+        Ok(match self.header.color_type {
+            GRAYSCALE => {
+                let gray = sample(0);
+                let alpha = if transparent(&[gray]) { 0 } else { 255 };
+                let gray = to_u8(gray);
+                [gray, gray, gray, alpha]
+            }
+            RGB => {
+                let rgb = [sample(0), sample(1), sample(2)];
+                let alpha = if transparent(&rgb) { 0 } else { 255 };
+                let [r, g, b] = rgb.map(to_u8);
+                [r, g, b, alpha]
+            }
+            PALETTE => {
+                let index = sample(0) as usize;
+                let color = self
+                    .palette
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or_else(|| format!("the palette has no color {index}"))?;
+                let alpha = self.transparency.get(index).copied().unwrap_or(255);
+                [color[0], color[1], color[2], alpha]
+            }
+            GRAYSCALE_ALPHA => {
+                let gray = to_u8(sample(0));
+                [gray, gray, gray, to_u8(sample(1))]
+            }
+            _ => [0, 1, 2, 3].map(|c| to_u8(sample(c))),
+        })
+    }
+}
+
+// the `index`th value of a row of values of `bit_depth` bits
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+        }
+    }
+}
+
+fn to_u8(value: u16, bit_depth: u8) -> u8 {
+    match bit_depth {
+        16 => (value >> 8) as u8,
+        8 => value as u8,
+        _ => (value * 255 / ((1 << bit_depth) - 1)) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], content: &[u8]) {
+        let mut crc = Crc::new();
+        crc.update(kind);
+        crc.update(content);
+        png.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(content);
+        png.extend_from_slice(&crc.sum().to_be_bytes());
+    }
+
+    // a png of the rows of `filtered`, which start with their filter type, and of the chunks
+    // in `extra` between the header and the data
+    fn png(
+        (width, height): (u32, u32),
+        bit_depth: u8,
+        color_type: u8,
+        interlaced: bool,
+        extra: &[(&[u8; 4], &[u8])],
+        filtered: &[u8],
+    ) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[bit_depth, color_type, 0, 0, interlaced as u8]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(filtered).unwrap();
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        for (kind, content) in extra {
+            chunk(&mut png, kind, content);
+        }
+        chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn palette_with_transparency() {
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        // the colors after the end of the tRNS chunk are opaque
+        let transparency = [0, 128];
+        let data = png(
+            (3, 1),
+            8,
+            PALETTE,
+            false,
+            &[(b"PLTE", &palette), (b"tRNS", &transparency)],
+            &[0, 0, 1, 2],
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        assert_eq!(texture.data, [255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn palette_sub_byte_indices() {
+        let palette = [10, 10, 10, 20, 20, 20, 30, 30, 30, 40, 40, 40];
+        // 2 bits indices 3 0 1 2 3
+        let data = png(
+            (5, 1),
+            2,
+            PALETTE,
+            false,
+            &[(b"PLTE", &palette)],
+            &[0, 0b1100_0110, 0b1100_0000],
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        let reds: Vec<u8> = texture.data.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(reds, [40, 10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn rgb_16_bits_with_transparent_color() {
+        let transparent = [0x12, 0x34, 0, 0, 0xff, 0xff];
+        let data = png(
+            (2, 1),
+            16,
+            RGB,
+            false,
+            &[(b"tRNS", &transparent)],
+            &[
+                0, 0x12, 0x34, 0, 0, 0xff, 0xff, 0xab, 0xcd, 0x80, 0, 0x01, 0xff,
+            ],
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        assert_eq!(texture.data, [0x12, 0, 0xff, 0, 0xab, 0x80, 0x01, 255]);
+    }
+
+    #[test]
+    fn sub_byte_grayscale() {
+        // 1 bit on the first row and its 9th pixel in the next byte
+        let data = png(
+            (9, 1),
+            1,
+            GRAYSCALE,
+            false,
+            &[],
+            &[0, 0b1010_0001, 0b1000_0000],
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        let grays: Vec<u8> = texture.data.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(grays, [255, 0, 255, 0, 0, 0, 0, 255, 255]);
+
+        // 4 bits on 2 rows of 3 pixels
+        let data = png(
+            (3, 2),
+            4,
+            GRAYSCALE,
+            false,
+            &[],
+            &[0, 0x0f, 0x50, 0, 0xa3, 0x10],
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        let grays: Vec<u8> = texture.data.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(grays, [0, 255, 85, 170, 51, 17]);
+        assert!(texture.data.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn filters() {
+        // the same 2x5 gray and alpha image with each of the filters, a row of 4 bytes whose
+        // value is its index plus 10 times the row
+        let bpp = 2;
+        let mut filtered = Vec::new();
+        let mut previous = [0u8; 4];
+        for (y, filter) in (0..5u8).enumerate() {
+            let row: [u8; 4] = std::array::from_fn(|i| i as u8 + 10 * y as u8);
+            filtered.push(filter);
+            for i in 0..4 {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => previous[i],
+                    3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+                    _ => paeth(left, previous[i], up_left),
+                };
+                filtered.push(row[i].wrapping_sub(predictor));
+            }
+            previous = row;
+        }
+        let data = png((2, 5), 8, GRAYSCALE_ALPHA, false, &[], &filtered);
+        let texture = Texture::parse_png(&data).unwrap();
+        for y in 0..5 {
+            for x in 0..2 {
+                let i = (y * 2 + x) * 4;
+                let (gray, alpha) = ((x * 2 + y * 10) as u8, (x * 2 + 1 + y * 10) as u8);
+                assert_eq!(texture.data[i..i + 4], [gray, gray, gray, alpha]);
+            }
+        }
+    }
+
+    #[test]
+    fn adam7() {
+        // a 5x6 rgba image whose pixels are their coordinates, stored pass by pass
+        let (width, height) = (5, 6);
+        let pixel = |x: usize, y: usize| [x as u8, y as u8, (x * y) as u8, 200];
+        let mut filtered = Vec::new();
+        for (x0, y0, dx, dy) in ADAM7 {
+            for y in (y0..height).step_by(dy) {
+                if x0 >= width {
+                    break;
+                }
+                filtered.push(0);
+                for x in (x0..width).step_by(dx) {
+                    filtered.extend_from_slice(&pixel(x, y));
+                }
+            }
+        }
+        let data = png(
+            (width as u32, height as u32),
+            8,
+            RGB_ALPHA,
+            true,
+            &[],
+            &filtered,
+        );
+        let texture = Texture::parse_png(&data).unwrap();
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) * 4;
+                assert_eq!(texture.data[i..i + 4], pixel(x, y), "pixel {x} {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = png((2, 2), 8, GRAYSCALE, false, &[], &[0, 1, 2, 0, 3, 4]);
+        for len in [0, 4, PNG_SIGNATURE.len() + 10, data.len() - 13] {
+            assert!(Texture::parse_png(&data[..len]).is_err(), "{len} bytes");
+        }
+        // a row is missing from the data
+        let data = png((2, 2), 8, GRAYSCALE, false, &[], &[0, 1, 2]);
+        assert!(Texture::parse_png(&data).is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let valid = png((1, 1), 8, GRAYSCALE, false, &[], &[0, 7]);
+        assert!(Texture::parse_png(&valid).is_ok());
+
+        let mut bad_crc = valid.clone();
+        bad_crc[PNG_SIGNATURE.len() + 8] ^= 1;
+        assert!(Texture::parse_png(&bad_crc).is_err());
+        let mut bad_signature = valid.clone();
+        bad_signature[1] = b'Q';
+        assert!(Texture::parse_png(&bad_signature).is_err());
+
+        let invalid = [
+            // a bit depth the color type does not have
+            png((1, 1), 4, RGB, false, &[], &[0, 1, 2, 3]),
+            // no palette
+            png((1, 1), 8, PALETTE, false, &[], &[0, 0]),
+            // an index out of the palette
+            png((1, 1), 8, PALETTE, false, &[(b"PLTE", &[1, 2, 3])], &[0, 1]),
+            // an unknown filter type
+            png((1, 1), 8, GRAYSCALE, false, &[], &[5, 7]),
+            // an unknown critical chunk
+            png((1, 1), 8, GRAYSCALE, false, &[(b"ABCD", &[])], &[0, 7]),
+            png((0, 1), 8, GRAYSCALE, false, &[], &[]),
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            assert!(Texture::parse_png(data).is_err(), "image {i}");
+        }
+    }
+}
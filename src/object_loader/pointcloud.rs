@@ -0,0 +1,197 @@
+use super::{Group, Object, Submesh, Topology, Vertexxx};
+use crate::{math::Vec3, vec3};
+use std::error::Error;
+
+impl Object {
+    // a point cloud, its single submesh covers all the vertices
+    pub fn from_points(vertex: Vec<Vertexxx>) -> Self {
+        let mut min = vec3!(f32::MAX);
+        let mut max = vec3!(f32::MIN);
+        for vertex in vertex.iter() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+
+        Self {
+            indice: Vec::new(),
+            size: max - min,
+            center: (min + max) * 0.5,
+            mtllibs: Vec::new(),
+            materials: Vec::new(),
+            submeshes: vec![Submesh {
+                material: None,
+                group: 0,
+                start: 0,
+                count: vertex.len() as u32,
+            }],
+            groups: vec![Group {
+                name: "default".to_string(),
+                min,
+                max,
+            }],
+            topology: Topology::Points,
+            vertex,
+        }
+    }
+
+    // loads an xyz or pts point cloud, a point per line. The columns after the position
+    // depend on their number:
+    //   3: x y z
+    //   4: x y z intensity
+    //   6: x y z r g b, or x y z nx ny nz when every such vector has a unit length
+    //   7: x y z intensity r g b
+    //   9: x y z r g b nx ny nz
+    // colors are either integers up to 255 or floats up to 1. The lines with a single value
+    // are the point counts of pts files, they are skipped
+    pub fn parse_point_cloud(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let file = std::str::from_utf8(data)?;
+
+        let mut columns = 0;
+        let mut values: Vec<f32> = Vec::new();
+        for (line_number, line) in file.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+
+            let start = values.len();
+            for token in line
+                .split(|c: char| c.is_ascii_whitespace() || c == ',' || c == ';')
+                .filter(|token| !token.is_empty())
+            {
+                values.push(
+                    token
+                        .parse()
+                        .map_err(|_| format!("line {line_number}: invalid value {token}"))?,
+                );
+            }
+            match values.len() - start {
+                1 => values.truncate(start),
+                count if columns == 0 && [3, 4, 6, 7, 9].contains(&count) => columns = count,
+                count if count == columns => {}
+                count if columns == 0 => {
+                    return Err(format!("line {line_number}: {count} values per point").into())
+                }
+                _ => return Err(format!("line {line_number}: expected {columns} values").into()),
+            }
+        }
+        if values.is_empty() {
+            return Err("the point cloud has no points".into());
+        }
+
+        let rows = values.chunks_exact(columns);
+        let (color, normal) = match columns {
+            6 if rows.clone().all(|row| is_unit(&row[3..6])) => (None, Some(3)),
+            6 => (Some(3), None),
+            7 => (Some(4), None),
+            9 => (Some(3), Some(6)),
+            _ => (None, None),
+        };
+        let integer_colors = color.is_some_and(|c| {
+            rows.clone()
+                .any(|row| row[c..c + 3].iter().any(|&v| v > 1.0))
+        });
+        let color_scale = if integer_colors { 255.0 } else { 1.0 };
+
+        let points = rows
+            .map(|row| Vertexxx {
+                position: [row[0], row[1], row[2]],
+                normal: normal.map_or([0.0; 3], |n| [row[n], row[n + 1], row[n + 2]]),
+                color: color.map_or([1.0; 3], |c| {
+                    [row[c], row[c + 1], row[c + 2]].map(|v| v / color_scale)
+                }),
+                texture: [0.0; 2],
+            })
+            .collect();
+        Ok(Self::from_points(points))
+    }
+}
+
+fn is_unit(vector: &[f32]) -> bool {
+    let length = Vec3::from(&[vector[0], vector[1], vector[2]]).length();
+    (length - 1.0).abs() < 1e-2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::{loader::mesh_loader, object::ParseOptions, Topology};
+    use std::path::Path;
+
+    fn parse(data: &str) -> Result<Object, Box<dyn Error>> {
+        Object::parse_point_cloud(data.as_bytes())
+    }
+
+    #[test]
+    fn xyz_and_pts() {
+        for name in ["scan.xyz", "scan.PTS", "scan.xyz.gz"] {
+            let path = Path::new(name);
+            let loader = mesh_loader(path);
+            assert_eq!(loader.name(), "point cloud", "{name}");
+
+            // a pts file starts with its point count
+            let data = b"3\n0 0 0\n2,4,6\n-2;0;2\n";
+            let (object, warnings) = loader.parse(data, path, &ParseOptions::default()).unwrap();
+            assert!(warnings.is_empty());
+            assert_eq!(object.topology, Topology::Points);
+            assert!(object.indice.is_empty());
+            assert_eq!(object.vertex.len(), 3);
+            assert_eq!(object.vertex[1].position, [2.0, 4.0, 6.0]);
+            assert_eq!(object.vertex[1].color, [1.0; 3]);
+            assert_eq!(object.submeshes[0].count, 3);
+            assert_eq!(object.size.to_array(), [4.0, 4.0, 6.0]);
+            assert_eq!(object.center.to_array(), [0.0, 2.0, 3.0]);
+        }
+    }
+
+    #[test]
+    fn columns() {
+        // intensity
+        let object = parse("# comment\n\n1 2 3 0.5\n4 5 6 0.5\n").unwrap();
+        assert_eq!(object.vertex[1].position, [4.0, 5.0, 6.0]);
+        assert_eq!(object.vertex[1].color, [1.0; 3]);
+
+        // integer colors
+        let object = parse("0 0 0 255 0 0\n1 1 1 0 0 255\n").unwrap();
+        assert_eq!(object.vertex[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(object.vertex[1].color, [0.0, 0.0, 1.0]);
+        assert_eq!(object.vertex[1].normal, [0.0; 3]);
+
+        // normals, all of them have a unit length
+        let object = parse("0 0 0 1 0 0\n1 1 1 0 0 1\n").unwrap();
+        assert_eq!(object.vertex[0].normal, [1.0, 0.0, 0.0]);
+        assert_eq!(object.vertex[1].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(object.vertex[1].color, [1.0; 3]);
+
+        // float colors, the first one only looks like a normal
+        let object = parse("0 0 0 1 0 0\n1 1 1 0.5 0.5 0.5\n").unwrap();
+        assert_eq!(object.vertex[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(object.vertex[0].normal, [0.0; 3]);
+
+        // intensity and colors
+        let object = parse("// comment\n0 0 0 -1200 255 0 0\n").unwrap();
+        assert_eq!(object.vertex[0].color, [1.0, 0.0, 0.0]);
+
+        // colors and normals
+        let object = parse("0 0 0 0.5 0.5 0.5 0 1 0\n").unwrap();
+        assert_eq!(object.vertex[0].color, [0.5; 3]);
+        assert_eq!(object.vertex[0].normal, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn invalid() {
+        for data in [
+            "",
+            "# only comments\n12\n",
+            "0 0\n",
+            "0 0 0 1 1\n",
+            "0 0 0\n1 1 1 1\n",
+            "0 0 zero\n",
+        ] {
+            assert!(parse(data).is_err(), "{data}");
+        }
+    }
+}
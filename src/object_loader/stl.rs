@@ -0,0 +1,336 @@
+use super::{
+    normals::Triangle,
+    object::{ObjParser, ParseOptions},
+    triangulate::triangulate,
+    Object, Vertexxx,
+};
+use crate::math::Vec3;
+use std::{collections::HashMap, error::Error};
+
+const HEADER_SIZE: usize = 80;
+// normal, 3 vertices and a 16 bits attribute
+const FACET_SIZE: usize = 50;
+
+impl Object {
+    // loads an ascii or binary stl file. The facets keep their normal (it is computed when
+    // the file leaves it null) and identical positions are welded so that vertices can be
+    // shared. Each solid of an ascii file is a group
+    pub fn parse_stl(data: &[u8], options: &ParseOptions) -> Result<Self, Box<dyn Error>> {
+        let mut builder = StlBuilder {
+            parser: ObjParser::new(options),
+            positions: HashMap::new(),
+        };
+        if is_binary(data) {
+            parse_binary(data, &mut builder)?;
+        } else {
+            parse_ascii(std::str::from_utf8(data)?, &mut builder)?;
+        }
+
+        let (obj, _) = builder.parser.finish();
+        Ok(obj)
+    }
+}
+
+// binary files may start with "solid" as well, their size is what tells them apart first
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() >= HEADER_SIZE + 4 {
+        let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+        if data.len() == HEADER_SIZE + 4 + count as usize * FACET_SIZE {
+            return true;
+        }
+    }
+    // ascii files never contain null bytes, unlike binary ones
+    !data.trim_ascii_start().starts_with(b"solid") || data.contains(&0)
+}
+
+fn parse_binary(data: &[u8], builder: &mut StlBuilder) -> Result<(), Box<dyn Error>> {
+    if data.len() < HEADER_SIZE + 4 {
+        return Err("binary stl too short".into());
+    }
+    let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+    let facets = &data[HEADER_SIZE + 4..];
+    if facets.len() < count as usize * FACET_SIZE {
+        return Err(format!(
+            "binary stl truncated, {count} facets announced but only {} found",
+            facets.len() / FACET_SIZE
+        )
+        .into());
+    }
+
+    for facet in facets.chunks_exact(FACET_SIZE).take(count as usize) {
+        let value = |i: usize| f32::from_le_bytes(facet[i * 4..i * 4 + 4].try_into().unwrap());
+        let vector = |i: usize| [value(i * 3), value(i * 3 + 1), value(i * 3 + 2)];
+        builder.push_facet(vector(0), &[vector(1), vector(2), vector(3)]);
+    }
+    Ok(())
+}
+
+fn parse_ascii(file: &str, builder: &mut StlBuilder) -> Result<(), Box<dyn Error>> {
+    let mut normal = [0.0; 3];
+    let mut polygon = Vec::new();
+
+    for (line_number, line) in file.lines().enumerate() {
+        let line_number = line_number + 1;
+        let line: Vec<&str> = line.split_ascii_whitespace().collect();
+
+        match line.first().copied() {
+            Some("solid") => match line[1..].join(" ") {
+                name if name.is_empty() => builder.parser.start_group("default"),
+                name => builder.parser.start_group(&name),
+            },
+            Some("facet") => {
+                normal = match line.get(1) {
+                    Some(&"normal") => parse_vector(&line[2..], line_number)?,
+                    _ => [0.0; 3],
+                };
+                polygon.clear();
+            }
+            Some("vertex") => polygon.push(parse_vector(&line[1..], line_number)?),
+            Some("endfacet") => {
+                if polygon.len() < 3 {
+                    return Err(
+                        format!("line {line_number}: facet with less than 3 vertices").into(),
+                    );
+                }
+                builder.push_facet(normal, &polygon);
+                polygon.clear();
+            }
+            Some("outer" | "endloop" | "endsolid") | None => {}
+            Some(keyword) => {
+                return Err(format!("line {line_number}: unknown keyword {keyword}").into())
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_vector(line: &[&str], line_number: usize) -> Result<[f32; 3], Box<dyn Error>> {
+    match line {
+        [x, y, z] => Ok([x.parse()?, y.parse()?, z.parse()?]),
+        _ => Err(format!("line {line_number}: expected (x, y, z) format").into()),
+    }
+}
+
+struct StlBuilder<'a> {
+    parser: ObjParser<'a>,
+    // index of every position in the parser's list
+    positions: HashMap<[u32; 3], usize>,
+}
+
+impl StlBuilder<'_> {
+    fn push_facet(&mut self, normal: [f32; 3], polygon: &[[f32; 3]]) {
+        let normal = Vec3::from(&normal);
+        let has_normal = normal.length() > f32::EPSILON && normal.length().is_finite();
+
+        let corners: Vec<(Vertexxx, usize)> = polygon
+            .iter()
+            .map(|&position| {
+                let vertex = Vertexxx {
+                    position,
+                    normal: if has_normal {
+                        normal.normalize().to_array()
+                    } else {
+                        [0.0; 3]
+                    },
+                    color: [1.0; 3],
+                    ..Default::default()
+                };
+                (vertex, self.position_index(position))
+            })
+            .collect();
+
+        let triangles = if corners.len() == 3 {
+            vec![[0, 1, 2]]
+        } else {
+            triangulate(&polygon.iter().map(Vec3::from).collect::<Vec<_>>())
+        };
+        for [a, b, c] in triangles {
+            self.parser.triangles.push(Triangle {
+                corners: [corners[a].0, corners[b].0, corners[c].0],
+                positions: [corners[a].1, corners[b].1, corners[c].1],
+                smoothing_group: 0,
+                has_normal,
+            });
+        }
+    }
+
+    fn position_index(&mut self, position: [f32; 3]) -> usize {
+        let v = &mut self.parser.lists.v;
+        *self
+            .positions
+            .entry(position.map(f32::to_bits))
+            .or_insert_with(|| {
+                v.push([position, [1.0; 3]]);
+                v.len() - 1
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_loader::Topology;
+
+    type Facet = ([f32; 3], [[f32; 3]; 3]);
+
+    // a triangle flat on the z plane and a second one rising from their shared edge, the
+    // facets leave their normal null
+    const RIDGE: [Facet; 2] = [
+        (
+            [0.0; 3],
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        ),
+        (
+            [0.0; 3],
+            [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.5]],
+        ),
+    ];
+
+    fn binary(header: &[u8], facets: &[Facet]) -> Vec<u8> {
+        let mut data = header.to_vec();
+        data.resize(HEADER_SIZE, b' ');
+        data.extend((facets.len() as u32).to_le_bytes());
+        for (normal, vertices) in facets {
+            for value in normal.iter().chain(vertices.iter().flatten()) {
+                data.extend(value.to_le_bytes());
+            }
+            data.extend([0, 0]);
+        }
+        data
+    }
+
+    fn ascii(name: &str, facets: &[Facet]) -> String {
+        let mut data = format!("solid {name}\n");
+        for ([x, y, z], vertices) in facets {
+            data += &format!("  facet normal {x} {y} {z}\n    outer loop\n");
+            for [x, y, z] in vertices {
+                data += &format!("      vertex {x} {y} {z}\n");
+            }
+            data += "    endloop\n  endfacet\n";
+        }
+        data + &format!("endsolid {name}\n")
+    }
+
+    // the position and normal of every corner
+    fn corners(object: &Object) -> Vec<([f32; 3], [f32; 3])> {
+        object
+            .indice
+            .iter()
+            .map(|&i| {
+                let vertex = &object.vertex[i as usize];
+                (vertex.position, vertex.normal)
+            })
+            .collect()
+    }
+
+    fn vertex_count(object: &Object) -> usize {
+        let mut indices = object.indice.clone();
+        indices.sort();
+        indices.dedup();
+        indices.len()
+    }
+
+    #[test]
+    fn detection() {
+        let triangle: [Facet; 1] = [([0.0, 0.0, 1.0], RIDGE[0].1)];
+        assert!(!is_binary(ascii("part", &triangle).as_bytes()));
+        assert!(!is_binary(b"  \n solid\n"));
+        assert!(is_binary(&binary(b"binary", &triangle)));
+        // the size matches the facet count
+        assert!(is_binary(&binary(b"solid part", &triangle)));
+        // the size doesn't, but ascii files have no null bytes
+        let mut data = binary(b"solid part", &triangle);
+        data.extend(b"trailing");
+        assert!(is_binary(&data));
+    }
+
+    #[test]
+    fn ascii_and_binary() {
+        let facets: [Facet; 2] = [
+            ([0.0, 0.0, 2.0], RIDGE[0].1),
+            (
+                [0.0, 0.0, 0.0],
+                [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            ),
+        ];
+        let expected = [
+            ([0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+            // the null normal is computed from the winding
+            ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ];
+
+        let options = ParseOptions::default();
+        let object = Object::parse_stl(ascii("my part", &facets).as_bytes(), &options).unwrap();
+        assert_eq!(object.topology, Topology::Triangles);
+        assert_eq!(corners(&object), expected);
+        assert_eq!(object.groups.len(), 1);
+        assert_eq!(object.groups[0].name, "my part");
+
+        for header in [&b"binary"[..], b"solid my part"] {
+            let object = Object::parse_stl(&binary(header, &facets), &options).unwrap();
+            assert_eq!(corners(&object), expected);
+        }
+    }
+
+    #[test]
+    fn welding() {
+        let options = ParseOptions {
+            crease_angle: Some(60f32.to_radians()),
+            ..Default::default()
+        };
+        let objects = [
+            Object::parse_stl(ascii("ridge", &RIDGE).as_bytes(), &options).unwrap(),
+            Object::parse_stl(&binary(b"ridge", &RIDGE), &options).unwrap(),
+        ];
+        for object in objects {
+            let corners = corners(&object);
+            // the corner away from the ridge stays flat
+            assert_eq!(corners[0].1, [0.0, 0.0, 1.0]);
+            // the corners of the shared edge are smoothed between both facets
+            assert_eq!(corners[1], corners[4]);
+            assert_eq!(corners[2], corners[3]);
+            assert!(corners[1].1[2] < 1.0 && corners[1].1[0] < 0.0);
+            assert_eq!(vertex_count(&object), 4);
+        }
+
+        // without the crease angle, the facets keep their own normal
+        let object = Object::parse_stl(&binary(b"ridge", &RIDGE), &ParseOptions::default());
+        assert_eq!(vertex_count(&object.unwrap()), 6);
+    }
+
+    #[test]
+    fn quads() {
+        let data = "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n\
+                    vertex 1 1 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid\n";
+        let object = Object::parse_stl(data.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(object.indice.len(), 6);
+        assert_eq!(vertex_count(&object), 4);
+        assert_eq!(object.groups[0].name, "default");
+    }
+
+    #[test]
+    fn invalid() {
+        let options = ParseOptions::default();
+        let mut truncated = binary(b"binary", &RIDGE);
+        truncated.truncate(truncated.len() - 1);
+        assert!(Object::parse_stl(&truncated, &options).is_err());
+        assert!(Object::parse_stl(&[0; 40], &options).is_err());
+
+        for data in [
+            "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n\
+             endfacet\n",
+            "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 zero\n",
+            "solid\nfacet normal 0 0\n",
+            "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nmystery 1\n",
+        ] {
+            assert!(
+                Object::parse_stl(data.as_bytes(), &options).is_err(),
+                "{data}"
+            );
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use std::error::Error;
+use std::{
+    cmp::Ordering,
+    error::Error,
+    io::{self, Write},
+};
 
 #[derive(Debug, Clone)]
 pub struct Texture {
@@ -8,45 +12,504 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn parse_ppm(file: &str) -> Result<Self, Box<dyn Error>> {
-        let lines: Vec<&str> = file
-            .lines()
-            .filter(|l| !l.starts_with("#") && !l.is_empty())
-            .collect();
+    // loads a netpbm image: a pbm bitmap (P1, P4), a pgm grayscale (P2, P5), a ppm color
+    // image (P3, P6) or a pam (P7), which may have an alpha channel. The binary values take
+    // two bytes, in big endian, when the max value is above 255
+    pub fn parse_netpbm(file: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let header = parse_header(file)?;
+        if header.width == 0 || header.height == 0 {
+            return Err(format!("the image is {}x{}", header.width, header.height).into());
+        }
+        let pixels = (header.width as usize)
+            .checked_mul(header.height as usize)
+            .ok_or("the image is too big")?;
+        let count = pixels
+            .checked_mul(header.channels)
+            .ok_or("the image is too big")?;
+        let samples = read_samples(&file[header.len..], &header, count)?;
+
+        let max = header.max_value;
+        let mut data = Vec::with_capacity(pixels * 4);
+        for pixel in samples.chunks_exact(header.channels) {
+            let rgba = match *pixel {
+                [gray] => [gray, gray, gray, max],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [r, g, b] => [r, g, b, max],
+                [r, g, b, alpha] => [r, g, b, alpha],
+                _ => unreachable!("the header has from 1 to 4 channels"),
+            };
+            data.extend(rgba.map(|value| scale(value, max)));
+        }
+
+        Ok(Texture {
+            width: header.width,
+            height: header.height,
+            data,
+        })
+    }
+
+    // writes the image as a binary ppm, without its alpha channel
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.data.chunks_exact(4) {
+            out.write_all(&pixel[..3])?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    // P2, P3
+    Ascii,
+    // P5, P6, P7
+    Binary,
+    // P1, a digit per pixel, 1 being black
+    AsciiBits,
+    // P4, eight pixels per byte, each row starting on a new byte
+    PackedBits,
+}
 
-        if lines.len() < 3 {
-            return Err("file contains less than 3 lines".into());
+struct Header {
+    encoding: Encoding,
+    width: u32,
+    height: u32,
+    max_value: u16,
+    // 1 for gray, 2 for gray and alpha, 3 for rgb and 4 for rgb and alpha
+    channels: usize,
+    // where the pixels start
+    len: usize,
+}
+
+// the magic number is followed by the width, the height and the max value (which bitmaps
+// don't have), separated by whitespace and comments. A single whitespace character then
+// separates the header from the pixels. Pam files have their own header of named values
+fn parse_header(file: &[u8]) -> Result<Header, Box<dyn Error>> {
+    let (encoding, channels) = match file.get(..2) {
+        Some(b"P1") => (Encoding::AsciiBits, 1),
+        Some(b"P2") => (Encoding::Ascii, 1),
+        Some(b"P3") => (Encoding::Ascii, 3),
+        Some(b"P4") => (Encoding::PackedBits, 1),
+        Some(b"P5") => (Encoding::Binary, 1),
+        Some(b"P6") => (Encoding::Binary, 3),
+        Some(b"P7") => return parse_pam_header(file),
+        _ => {
+            let magic = String::from_utf8_lossy(&file[..file.len().min(2)]).into_owned();
+            return Err(format!("unsupported netpbm type {magic}").into());
         }
+    };
+
+    let mut tokens = Tokenizer { data: file, pos: 2 };
+    if !tokens.data.get(2).copied().is_some_and(is_whitespace) {
+        return Err("missing whitespace after the magic number".into());
+    }
+    let width = tokens.number("width")?;
+    let height = tokens.number("height")?;
+    let max_value = match encoding {
+        Encoding::AsciiBits | Encoding::PackedBits => 1,
+        _ => check_max_value(tokens.number("max value")?)?,
+    };
+    if !tokens
+        .data
+        .get(tokens.pos)
+        .copied()
+        .is_some_and(is_whitespace)
+    {
+        return Err("missing whitespace between the header and the pixels".into());
+    }
 
-        if lines[0] != "P3" {
-            return Err("only ppm files of the type P3 (rgb values for each pixel in ascii) are supported".into());
+    Ok(Header {
+        encoding,
+        width,
+        height,
+        max_value,
+        channels,
+        len: tokens.pos + 1,
+    })
+}
+
+// the header of a pam file is made of lines of a name and a value, in any order, which end
+// with ENDHDR. Comment lines start with a #
+fn parse_pam_header(file: &[u8]) -> Result<Header, Box<dyn Error>> {
+    let (mut width, mut height, mut depth, mut max_value) = (None, None, None, None);
+    let mut tuple_type = String::new();
+    let mut len = 2;
+    loop {
+        if len >= file.len() {
+            return Err("the pam header has no ENDHDR".into());
+        }
+        let end = file[len..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(file.len(), |i| len + i);
+        let line = std::str::from_utf8(&file[len..end])?.trim();
+        len = (end + 1).min(file.len());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
 
-        let size: Vec<&str> = lines[1].split(' ').collect();
-        let mut texture = Texture {
-            width: size[0].parse()?,
-            height: size[1].parse()?,
-            data: Vec::new(),
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+        let number = |name: &str| {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("invalid pam {name} {value}"))
         };
+        match key {
+            "ENDHDR" => break,
+            "WIDTH" => width = Some(number("width")?),
+            "HEIGHT" => height = Some(number("height")?),
+            "DEPTH" => depth = Some(number("depth")? as usize),
+            "MAXVAL" => max_value = Some(check_max_value(number("max value")?)?),
+            // the type may be split over several lines
+            "TUPLTYPE" => {
+                if !tuple_type.is_empty() {
+                    tuple_type.push(' ');
+                }
+                tuple_type.push_str(value);
+            }
+            _ => return Err(format!("unknown pam header line {line}").into()),
+        }
+    }
+
+    let depth = depth.ok_or("the pam header has no DEPTH")?;
+    let expected = match tuple_type.as_str() {
+        "BLACKANDWHITE" | "GRAYSCALE" => 1,
+        "BLACKANDWHITE_ALPHA" | "GRAYSCALE_ALPHA" => 2,
+        "RGB" => 3,
+        "RGB_ALPHA" => 4,
+        // the channels are then told by the depth
+        "" if (1..=4).contains(&depth) => depth,
+        _ => {
+            return Err(format!("unsupported pam tuple type {tuple_type} of depth {depth}").into())
+        }
+    };
+    if depth != expected {
+        return Err(
+            format!("the pam tuple type {tuple_type} does not have a depth of {depth}").into(),
+        );
+    }
 
-        let max_value: u16 = lines[2].parse()?;
+    Ok(Header {
+        encoding: Encoding::Binary,
+        width: width.ok_or("the pam header has no WIDTH")?,
+        height: height.ok_or("the pam header has no HEIGHT")?,
+        max_value: max_value.ok_or("the pam header has no MAXVAL")?,
+        channels: depth,
+        len,
+    })
+}
 
-        let mut i = 0;
-        for line in lines.iter().skip(3) {
-            for value in line.split_ascii_whitespace() {
-                let value = (value.parse::<f64>()? / max_value as f64) * 255.0;
-                texture.data.push(value as u8);
-                if i == 2 {
-                    texture.data.push(255);
+fn check_max_value(max_value: u32) -> Result<u16, Box<dyn Error>> {
+    match u16::try_from(max_value) {
+        Ok(max_value) if max_value > 0 => Ok(max_value),
+        _ => Err(format!("the max value {max_value} is not between 1 and 65535").into()),
+    }
+}
+
+// the whitespace of the netpbm spec, which includes the vertical tab
+fn is_whitespace(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || byte == 0x0b
+}
+
+// the tokens of a header or of ascii pixels, separated by whitespace and by comments, which
+// run from a # to the end of the line
+struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn skip_separators(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte == b'#' {
+                while self
+                    .data
+                    .get(self.pos)
+                    .is_some_and(|&b| b != b'\n' && b != b'\r')
+                {
+                    self.pos += 1;
                 }
-                i = (i + 1) % 3;
+            } else if is_whitespace(byte) {
+                self.pos += 1;
+            } else {
+                break;
             }
         }
+    }
 
-        if texture.data.len() as u32 != 4 * texture.height * texture.width {
-            return Err("The ppm file doesnt contain all the rgb values for its dimensions".into());
+    fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_separators();
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|&b| !is_whitespace(b) && b != b'#')
+        {
+            self.pos += 1;
         }
+        (self.pos > start).then(|| &self.data[start..self.pos])
+    }
+
+    // the next character which is not a separator, for the digits of P1 pixels, which
+    // don't need to be separated
+    fn byte(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let byte = self.data.get(self.pos).copied();
+        self.pos += 1;
+        byte
+    }
 
-        Ok(texture)
+    fn number(&mut self, name: &str) -> Result<u32, Box<dyn Error>> {
+        let token = self
+            .token()
+            .ok_or_else(|| format!("the header has no {name}"))?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| format!("invalid {name} {}", String::from_utf8_lossy(token)).into())
+    }
+}
+
+// the `count` values of the pixels, which are checked against the max value
+fn read_samples(body: &[u8], header: &Header, count: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+    let max = header.max_value;
+    let mut samples = Vec::with_capacity(count.min(body.len()));
+    match header.encoding {
+        Encoding::Ascii => {
+            let mut tokens = Tokenizer { data: body, pos: 0 };
+            while let Some(token) = tokens.token() {
+                let value = std::str::from_utf8(token)
+                    .ok()
+                    .and_then(|token| token.parse::<u16>().ok())
+                    .filter(|&value| value <= max)
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid value {}, the max value is {max}",
+                            String::from_utf8_lossy(token)
+                        )
+                    })?;
+                samples.push(value);
+            }
+            check_size(samples.len(), count, "values")?;
+        }
+        Encoding::AsciiBits => {
+            let mut tokens = Tokenizer { data: body, pos: 0 };
+            while let Some(digit) = tokens.byte() {
+                match digit {
+                    b'0' => samples.push(1),
+                    b'1' => samples.push(0),
+                    _ => return Err(format!("invalid bit {}", digit as char).into()),
+                }
+            }
+            check_size(samples.len(), count, "values")?;
+        }
+        Encoding::Binary => {
+            let sample_len = if max > 255 { 2 } else { 1 };
+            check_size(body.len(), count.saturating_mul(sample_len), "bytes")?;
+            if sample_len == 2 {
+                for value in body.chunks_exact(2) {
+                    samples.push(u16::from_be_bytes([value[0], value[1]]).min(max));
+                }
+            } else {
+                samples.extend(body.iter().map(|&value| (value as u16).min(max)));
+            }
+        }
+        Encoding::PackedBits => {
+            let width = header.width as usize;
+            let row_len = width.div_ceil(8);
+            check_size(
+                body.len(),
+                row_len.saturating_mul(header.height as usize),
+                "bytes",
+            )?;
+            for row in body.chunks_exact(row_len) {
+                for x in 0..width {
+                    let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+                    samples.push(1 - bit as u16);
+                }
+            }
+        }
+    }
+    Ok(samples)
+}
+
+// `found` values or bytes of pixels where the dimensions of the image need `expected` ones
+fn check_size(found: usize, expected: usize, unit: &str) -> Result<(), Box<dyn Error>> {
+    match found.cmp(&expected) {
+        Ordering::Less => {
+            Err(format!("the pixels are truncated, {found} {unit} instead of {expected}").into())
+        }
+        Ordering::Greater => {
+            Err(format!("the pixels are too long, {found} {unit} instead of {expected}").into())
+        }
+        Ordering::Equal => Ok(()),
+    }
+}
+
+// to the 0..=255 range of the texture
+fn scale(value: u16, max_value: u16) -> u8 {
+    ((value as u32 * 255 + max_value as u32 / 2) / max_value as u32) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(file: &[u8]) -> Result<Texture, Box<dyn Error>> {
+        Texture::parse_netpbm(file)
+    }
+
+    fn pixels(file: &[u8]) -> (u32, u32, Vec<u8>) {
+        let texture = parse(file).unwrap();
+        (texture.width, texture.height, texture.data)
+    }
+
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    #[test]
+    fn bitmaps() {
+        // 1 is black, the digits don't need to be separated
+        let expected = [WHITE, BLACK, BLACK, WHITE].concat();
+        assert_eq!(pixels(b"P1\n2 2\n01\n10\n"), (2, 2, expected.clone()));
+        assert_eq!(pixels(b"P1 2 2 0110"), (2, 2, expected));
+        // rows of 10 pixels take 2 bytes, the last 6 bits are padding
+        let file = [b"P4\n10 1\n".as_slice(), &[0b1000_0000, 0b0011_1111]].concat();
+        let (width, _, data) = pixels(&file);
+        assert_eq!(width, 10);
+        let mut expected = [WHITE; 10];
+        expected[0] = BLACK;
+        assert_eq!(data, expected.concat());
+    }
+
+    #[test]
+    fn grayscale_and_color() {
+        assert_eq!(
+            pixels(b"P2\n2 1\n4\n0 2\n"),
+            (2, 1, [BLACK, [128, 128, 128, 255]].concat())
+        );
+        assert_eq!(
+            pixels(b"P3\n1 2\n255\n1 2 3\n4 5 6\n"),
+            (1, 2, vec![1, 2, 3, 255, 4, 5, 6, 255])
+        );
+        let file = [b"P5\n2 1\n255\n".as_slice(), &[7, 200]].concat();
+        assert_eq!(
+            pixels(&file),
+            (2, 1, vec![7, 7, 7, 255, 200, 200, 200, 255])
+        );
+        let file = [b"P6\n1 1\n255\n".as_slice(), &[1, 2, 3]].concat();
+        assert_eq!(pixels(&file), (1, 1, vec![1, 2, 3, 255]));
+    }
+
+    #[test]
+    fn pam() {
+        let file = [
+            b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n".as_slice(),
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        ]
+        .concat();
+        assert_eq!(pixels(&file), (2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        // in any order, with comments, and the channels told by the depth alone
+        let file = [
+            b"P7\n# gray and alpha\nMAXVAL 255\nDEPTH 2\nHEIGHT 1\nWIDTH 1\nENDHDR\n".as_slice(),
+            &[9, 10],
+        ]
+        .concat();
+        assert_eq!(pixels(&file), (1, 1, vec![9, 9, 9, 10]));
+        let file = [
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 15\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n"
+                .as_slice(),
+            &[15, 0],
+        ]
+        .concat();
+        assert_eq!(pixels(&file), (1, 1, vec![255, 255, 255, 0]));
+    }
+
+    #[test]
+    fn comments() {
+        let file =
+            b"P2 # a comment\n# a line of comment\n2# width\n1\r# height\n3 # max\n0 # zero\n3\n";
+        assert_eq!(pixels(file), (2, 1, [BLACK, WHITE].concat()));
+        // on one line, separated by tabs, vertical tabs and several spaces
+        let file = [b"P6\t1\x0b1  255 ".as_slice(), &[1, 2, 3]].concat();
+        assert_eq!(pixels(&file), (1, 1, vec![1, 2, 3, 255]));
+        // the single whitespace after the max value ends the header, whatever follows
+        let file = [b"P5 1 1 255\n".as_slice(), b"#"].concat();
+        assert_eq!(pixels(&file), (1, 1, vec![35, 35, 35, 255]));
+    }
+
+    #[test]
+    fn wide_values() {
+        // above 255, the binary values take two bytes in big endian
+        let file = [b"P5\n2 1\n1000\n".as_slice(), &[0x01, 0xf4, 0x03, 0xe8]].concat();
+        let gray = |v| [v, v, v, 255];
+        assert_eq!(pixels(&file), (2, 1, [gray(128), gray(255)].concat()));
+        assert_eq!(
+            pixels(b"P3 1 1 65535 65535 0 32768"),
+            (1, 1, vec![255, 0, 128, 255])
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let invalid: [&[u8]; 9] = [
+            b"P1\n2 2\n011",
+            b"P2\n2 1\n255\n0",
+            b"P3\n1 1\n255\n1 2",
+            b"P4\n9 2\n\xff\xff\xff",
+            b"P5\n2 1\n255\n\x00",
+            b"P5\n1 1\n256\n\x00",
+            b"P6\n1 1\n255\n\x00\x00",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nMAXVAL 255\n",
+            b"P6\n1 1\n",
+        ];
+        for file in invalid {
+            let error = parse(file).unwrap_err().to_string();
+            assert!(!error.is_empty(), "{}", String::from_utf8_lossy(file));
+        }
+        assert!(parse(b"P2\n2 1\n255\n0 1 2").is_err());
+        assert!(parse(b"P5\n1 1\n255\n\x00\x00").is_err());
+    }
+
+    #[test]
+    fn oversized() {
+        // the dimensions are not trusted before the pixels are there
+        let invalid: [&[u8]; 5] = [
+            b"P6\n4294967295 4294967295\n255\n\x00\x00\x00",
+            b"P3\n4294967295 4294967295\n255\n0 0 0",
+            b"P4\n4294967295 4294967295\n\x00",
+            b"P1\n100000 100000\n0",
+            b"P7\nWIDTH 4294967295\nHEIGHT 4294967295\nDEPTH 4\nMAXVAL 65535\nENDHDR\n\x00",
+        ];
+        for file in invalid {
+            assert!(parse(file).is_err(), "{}", String::from_utf8_lossy(file));
+        }
+        assert!(parse(b"P5\n4294967296 1\n255\n\x00").is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        let invalid: [&[u8]; 16] = [
+            b"",
+            b"P",
+            b"P8\n1 1\n255\n\x00",
+            b"P62 1\n255\n\x00\x00\x00",
+            b"P5\n1\n",
+            b"P5\n1 -1\n255\n\x00",
+            b"P5\n0 1\n255\n",
+            b"P5\n1 1\n0\n\x00",
+            b"P5\n1 1\n65536\n\x00\x00",
+            b"P5\n1 1\n255",
+            b"P2\n1 1\n100\n101\n",
+            b"P2\n1 1\n100\nx\n",
+            b"P1\n1 1\n2\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE GRAYSCALE\nENDHDR\n\x00\x00\x00",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n\x00\x00\x00\x00\x00",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nCOLORS 3\nMAXVAL 255\nENDHDR\n\x00",
+        ];
+        for file in invalid {
+            assert!(parse(file).is_err(), "{}", String::from_utf8_lossy(file));
+        }
     }
 }
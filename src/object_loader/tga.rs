@@ -0,0 +1,327 @@
+use super::texture::Texture;
+use std::error::Error;
+
+const HEADER_SIZE: usize = 18;
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+// the run length encoded versions of the types above
+const RLE: u8 = 8;
+
+impl Texture {
+    // loads a color mapped, true color or grayscale tga image, uncompressed or run length
+    // encoded. Its rows go from the bottom to the top unless its descriptor says otherwise
+    pub fn parse_tga(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.len() < HEADER_SIZE {
+            return Err("invalid tga header".into());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]) as usize;
+        let id_len = data[0] as usize;
+        let has_map = data[1] == 1;
+        let image_type = data[2];
+        let (map_start, map_len, map_bits) = (u16_at(3), u16_at(5), data[7]);
+        let (width, height) = (u16_at(12), u16_at(14));
+        let bits = data[16];
+        let descriptor = data[17];
+        let alpha_bits = descriptor & 0x0f;
+        let right_to_left = descriptor & 0x10 != 0;
+        let top_down = descriptor & 0x20 != 0;
+        if width == 0 || height == 0 {
+            return Err(format!("the image is {width}x{height}").into());
+        }
+
+        // the format of the pixels, or of the colors of the map when the pixels are indices
+        let kind = image_type & !RLE;
+        let format = match kind {
+            COLOR_MAPPED if !has_map => return Err("the color map is missing".into()),
+            COLOR_MAPPED => Format::new(TRUE_COLOR, map_bits)
+                .map_err(|_| format!("unsupported tga color map entries of {map_bits} bits"))?,
+            TRUE_COLOR | GRAYSCALE => Format::new(kind, bits)?,
+            _ => return Err(format!("unsupported tga image type {image_type}").into()),
+        };
+        let has_alpha = alpha_bits > 0 || format == Format::GrayAlpha;
+        if kind == COLOR_MAPPED && bits != 8 && bits != 16 {
+            return Err(format!("unsupported tga color indices of {bits} bits").into());
+        }
+
+        let mut rest = data
+            .get(HEADER_SIZE + id_len..)
+            .ok_or("the file is truncated")?;
+        let mut map = Vec::new();
+        if has_map {
+            // the map of an image that is not color mapped is skipped
+            let entry_size = (map_bits as usize).div_ceil(8);
+            let map_size = map_len * entry_size;
+            let entries = rest.get(..map_size).ok_or("the color map is truncated")?;
+            if kind == COLOR_MAPPED {
+                map = entries
+                    .chunks_exact(entry_size)
+                    .map(|entry| format.rgba(entry, has_alpha))
+                    .collect();
+            }
+            rest = &rest[map_size..];
+        }
+
+        // the pixels in the order of the file, as their color index or as their bytes
+        let pixel_size = (bits as usize).div_ceil(8);
+        let count = width * height;
+        let pixels = if image_type & RLE != 0 {
+            decode_rle(rest, pixel_size, count)?
+        } else {
+            rest.get(..count * pixel_size)
+                .ok_or("the pixels are truncated")?
+                .to_vec()
+        };
+
+        let mut texture = Texture {
+            width: width as u32,
+            height: height as u32,
+            data: vec![0; count * 4],
+        };
+        for (i, pixel) in pixels.chunks_exact(pixel_size).enumerate() {
+            let rgba = if kind == COLOR_MAPPED {
+                let index = match *pixel {
+                    [index] => index as usize,
+                    [low, high] => u16::from_le_bytes([low, high]) as usize,
+                    _ => unreachable!(),
+                };
+                *index
+                    .checked_sub(map_start)
+                    .and_then(|index| map.get(index))
+                    .ok_or_else(|| format!("the color map has no color {index}"))?
+            } else {
+                format.rgba(pixel, has_alpha)
+            };
+            let (mut x, mut y) = (i % width, i / width);
+            if right_to_left {
+                x = width - 1 - x;
+            }
+            if !top_down {
+                y = height - 1 - y;
+            }
+            let j = (y * width + x) * 4;
+            texture.data[j..j + 4].copy_from_slice(&rgba);
+        }
+        Ok(texture)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gray,
+    GrayAlpha,
+    // 5 bits per channel with a bit of alpha
+    Bgr5,
+    Bgr,
+    Bgra,
+}
+
+impl Format {
+    // the format of the pixels of `bits` bits of an image of `kind`
+    fn new(kind: u8, bits: u8) -> Result<Self, Box<dyn Error>> {
+        match (kind, bits) {
+            (GRAYSCALE, 8) => Ok(Self::Gray),
+            (GRAYSCALE, 16) => Ok(Self::GrayAlpha),
+            (TRUE_COLOR, 15 | 16) => Ok(Self::Bgr5),
+            (TRUE_COLOR, 24) => Ok(Self::Bgr),
+            (TRUE_COLOR, 32) => Ok(Self::Bgra),
+            _ => Err(format!("unsupported tga pixels of {bits} bits").into()),
+        }
+    }
+
+    fn rgba(self, pixel: &[u8], has_alpha: bool) -> [u8; 4] {
+        let opaque = |alpha| if has_alpha { alpha } else { 255 };
+        match self {
+            Self::Gray => [pixel[0], pixel[0], pixel[0], 255],
+            Self::GrayAlpha => [pixel[0], pixel[0], pixel[0], pixel[1]],
+            Self::Bgr5 => {
+                let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+                let channel = |shift: u16| (((value >> shift) & 0x1f) * 255 / 31) as u8;
+                let alpha = if value & 0x8000 != 0 { 255 } else { 0 };
+                [channel(10), channel(5), channel(0), opaque(alpha)]
+            }
+            Self::Bgr => [pixel[2], pixel[1], pixel[0], 255],
+            Self::Bgra => [pixel[2], pixel[1], pixel[0], opaque(pixel[3])],
+        }
+    }
+}
+
+// the `count` pixels of `pixel_size` bytes encoded by packets of either repeated or raw
+// pixels. A packet may span several rows
+fn decode_rle(mut data: &[u8], pixel_size: usize, count: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut pixels = Vec::with_capacity(count * pixel_size);
+    while pixels.len() < count * pixel_size {
+        let (&packet, rest) = data.split_first().ok_or("the pixels are truncated")?;
+        let len = (packet & 0x7f) as usize + 1;
+        if packet & 0x80 != 0 {
+            let pixel = rest.get(..pixel_size).ok_or("the pixels are truncated")?;
+            for _ in 0..len {
+                pixels.extend_from_slice(pixel);
+            }
+            data = &rest[pixel_size..];
+        } else {
+            let raw = rest
+                .get(..len * pixel_size)
+                .ok_or("the pixels are truncated")?;
+            pixels.extend_from_slice(raw);
+            data = &rest[len * pixel_size..];
+        }
+    }
+    // the last packet may go beyond the image
+    pixels.truncate(count * pixel_size);
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a tga of `kind` with the color map of `map_bits` bits entries starting at `map_start`
+    // when `map` is not empty, followed by the `pixels` as they are stored
+    #[allow(clippy::too_many_arguments)]
+    fn tga(
+        image_type: u8,
+        (width, height): (u16, u16),
+        bits: u8,
+        descriptor: u8,
+        map_start: u16,
+        map_bits: u8,
+        map: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let map_len = match map_bits {
+            0 => 0,
+            bits => map.len() / (bits as usize).div_ceil(8),
+        };
+        let mut data = vec![0, !map.is_empty() as u8, image_type];
+        data.extend_from_slice(&map_start.to_le_bytes());
+        data.extend_from_slice(&(map_len as u16).to_le_bytes());
+        data.push(map_bits);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&[bits, descriptor]);
+        data.extend_from_slice(map);
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    fn grays(texture: &Texture) -> Vec<u8> {
+        texture.data.chunks(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn origins() {
+        // 2x2 grayscale pixels 1 2 3 4 in the order of the file
+        let pixels = [1, 2, 3, 4];
+        let image = |descriptor| {
+            let data = tga(GRAYSCALE, (2, 2), 8, descriptor, 0, 0, &[], &pixels);
+            grays(&Texture::parse_tga(&data).unwrap())
+        };
+        // bottom left by default
+        assert_eq!(image(0), [3, 4, 1, 2]);
+        assert_eq!(image(0x10), [4, 3, 2, 1]);
+        assert_eq!(image(0x20), [1, 2, 3, 4]);
+        assert_eq!(image(0x30), [2, 1, 4, 3]);
+    }
+
+    #[test]
+    fn rle_packets_spanning_rows() {
+        // 3x2 top down, a run of 4 then 2 raw pixels, so the run ends on the second row
+        let pixels = [0x83, 9, 0x01, 5, 6];
+        let data = tga(GRAYSCALE | RLE, (3, 2), 8, 0x20, 0, 0, &[], &pixels);
+        assert_eq!(
+            grays(&Texture::parse_tga(&data).unwrap()),
+            [9, 9, 9, 9, 5, 6]
+        );
+
+        // a run going beyond the last pixel
+        let pixels = [0x81, 1, 2, 3, 0xff, 7, 8, 9];
+        let data = tga(TRUE_COLOR | RLE, (3, 1), 24, 0x20, 0, 0, &[], &pixels);
+        assert_eq!(
+            Texture::parse_tga(&data).unwrap().data,
+            [3, 2, 1, 255, 3, 2, 1, 255, 9, 8, 7, 255]
+        );
+    }
+
+    #[test]
+    fn true_color() {
+        // 32 bits with 8 bits of alpha, then without them
+        let pixels = [1, 2, 3, 4];
+        let data = tga(TRUE_COLOR, (1, 1), 32, 0x08, 0, 0, &[], &pixels);
+        assert_eq!(Texture::parse_tga(&data).unwrap().data, [3, 2, 1, 4]);
+        let data = tga(TRUE_COLOR, (1, 1), 32, 0, 0, 0, &[], &pixels);
+        assert_eq!(Texture::parse_tga(&data).unwrap().data, [3, 2, 1, 255]);
+
+        // 16 bits as arrrrrgggggbbbbb, with an alpha bit
+        let pixel = 0b1_11111_00000_10000_u16.to_le_bytes();
+        let data = tga(TRUE_COLOR, (1, 1), 16, 0x01, 0, 0, &[], &pixel);
+        assert_eq!(Texture::parse_tga(&data).unwrap().data, [255, 0, 131, 255]);
+
+        // the color map of an image that is not color mapped is skipped
+        let map = [9; 6];
+        let data = tga(TRUE_COLOR, (1, 1), 32, 0, 0, 24, &map, &pixels);
+        assert_eq!(Texture::parse_tga(&data).unwrap().data, [3, 2, 1, 255]);
+    }
+
+    #[test]
+    fn color_mapped() {
+        // a map of 24 bits colors from the index 2, and 8 bits indices
+        let map = [0, 0, 255, 0, 255, 0];
+        let data = tga(COLOR_MAPPED, (2, 1), 8, 0x20, 2, 24, &map, &[3, 2]);
+        assert_eq!(
+            Texture::parse_tga(&data).unwrap().data,
+            [0, 255, 0, 255, 255, 0, 0, 255]
+        );
+
+        // 16 bits indices in run length encoded packets, of a map of 32 bits colors
+        let map = [1, 2, 3, 4, 5, 6, 7, 8];
+        let pixels = [0x81, 1, 0, 0x00, 0, 0];
+        let data = tga(COLOR_MAPPED | RLE, (3, 1), 16, 0x28, 0, 32, &map, &pixels);
+        assert_eq!(
+            Texture::parse_tga(&data).unwrap().data,
+            [7, 6, 5, 8, 7, 6, 5, 8, 3, 2, 1, 4]
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let data = tga(TRUE_COLOR, (2, 1), 24, 0, 0, 0, &[], &[1, 2, 3, 4, 5, 6]);
+        assert!(Texture::parse_tga(&data).is_ok());
+        for len in [0, HEADER_SIZE - 1, data.len() - 1] {
+            assert!(Texture::parse_tga(&data[..len]).is_err(), "{len} bytes");
+        }
+
+        let invalid = [
+            // a run without its pixel and raw pixels cut short
+            tga(GRAYSCALE | RLE, (2, 1), 8, 0, 0, 0, &[], &[0x81]),
+            tga(GRAYSCALE | RLE, (2, 1), 8, 0, 0, 0, &[], &[0x01, 1]),
+            // a color map shorter than its length
+            tga(COLOR_MAPPED, (1, 1), 8, 0, 0, 24, &[1, 2, 3], &[])[..HEADER_SIZE + 2].to_vec(),
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            assert!(Texture::parse_tga(data).is_err(), "image {i}");
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let invalid = [
+            tga(GRAYSCALE, (0, 1), 8, 0, 0, 0, &[], &[]),
+            // unknown image types and depths
+            tga(4, (1, 1), 8, 0, 0, 0, &[], &[0]),
+            tga(GRAYSCALE, (1, 1), 24, 0, 0, 0, &[], &[0; 3]),
+            tga(TRUE_COLOR, (1, 1), 8, 0, 0, 0, &[], &[0]),
+            // color mapped without a map, with entries of 0 bits or indices of 24 bits
+            tga(COLOR_MAPPED, (1, 1), 8, 0, 0, 0, &[], &[0]),
+            tga(COLOR_MAPPED, (1, 1), 8, 0, 0, 0, &[1], &[0]),
+            tga(COLOR_MAPPED, (1, 1), 24, 0, 0, 24, &[1, 2, 3], &[0; 3]),
+            // an index before the start of the map and one after its end
+            tga(COLOR_MAPPED, (1, 1), 8, 0, 1, 24, &[1, 2, 3], &[0]),
+            tga(COLOR_MAPPED, (1, 1), 8, 0, 0, 24, &[1, 2, 3], &[1]),
+        ];
+        for (i, data) in invalid.iter().enumerate() {
+            assert!(Texture::parse_tga(data).is_err(), "image {i}");
+        }
+    }
+}
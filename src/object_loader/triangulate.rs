@@ -0,0 +1,282 @@
+use crate::{math::Vec3, vec3};
+
+// splits a polygon (in its winding order) into triangles, returned as indices into `polygon`
+// the polygon is projected onto its best-fit plane and then ear clipped, so concave and
+// slightly non-planar faces are handled as well
+pub fn triangulate(polygon: &[Vec3]) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let normal = newell_normal(polygon);
+    if normal.length() <= f32::EPSILON {
+        // degenerate polygon, there is no plane to project on
+        return fan(n);
+    }
+    let normal = normal.normalize();
+
+    // orthonormal basis of the plane, (u, v, normal) is right handed so the projected
+    // polygon keeps a counter clockwise winding
+    let axis = if normal.x.abs() < 0.9 {
+        vec3!(1.0, 0.0, 0.0)
+    } else {
+        vec3!(0.0, 1.0, 0.0)
+    };
+    let u = Vec3::cross(&axis, &normal).normalize();
+    let v = Vec3::cross(&normal, &u);
+    let points: Vec<[f32; 2]> = polygon
+        .iter()
+        .map(|p| [Vec3::dot(p, &u), Vec3::dot(p, &v)])
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len)
+            .find(|&i| is_ear(&points, &remaining, i))
+            // self intersecting or numerically unstable polygons may have no ear left,
+            // clipping the most convex corner always lets us terminate
+            .unwrap_or_else(|| most_convex_corner(&points, &remaining));
+
+        let prev = remaining[(ear + len - 1) % len];
+        let next = remaining[(ear + 1) % len];
+        triangles.push([prev, remaining[ear], next]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+// normal of the best-fit plane of the polygon, its length is twice the polygon area
+fn newell_normal(polygon: &[Vec3]) -> Vec3 {
+    let mut normal = vec3!(0.0);
+    for (i, cur) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        normal.x += (cur.y - next.y) * (cur.z + next.z);
+        normal.y += (cur.z - next.z) * (cur.x + next.x);
+        normal.z += (cur.x - next.x) * (cur.y + next.y);
+    }
+    normal
+}
+
+fn fan(n: usize) -> Vec<[usize; 3]> {
+    (1..n - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+fn corner(points: &[[f32; 2]], remaining: &[usize], i: usize) -> ([f32; 2], [f32; 2], [f32; 2]) {
+    let len = remaining.len();
+    (
+        points[remaining[(i + len - 1) % len]],
+        points[remaining[i]],
+        points[remaining[(i + 1) % len]],
+    )
+}
+
+fn cross_2d(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn is_ear(points: &[[f32; 2]], remaining: &[usize], i: usize) -> bool {
+    let (a, b, c) = corner(points, remaining, i);
+    if cross_2d(a, b, c) <= 0.0 {
+        // reflex or flat corner
+        return false;
+    }
+
+    let len = remaining.len();
+    let triangle = [
+        remaining[(i + len - 1) % len],
+        remaining[i],
+        remaining[(i + 1) % len],
+    ];
+    remaining
+        .iter()
+        .filter(|j| !triangle.contains(j))
+        .map(|&j| points[j])
+        // points sitting exactly on a corner of the ear (duplicated vertices) don't block it
+        .filter(|&p| p != a && p != b && p != c)
+        .all(|p| !point_in_triangle(p, a, b, c))
+}
+
+fn point_in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross_2d(a, b, p) >= 0.0 && cross_2d(b, c, p) >= 0.0 && cross_2d(c, a, p) >= 0.0
+}
+
+fn most_convex_corner(points: &[[f32; 2]], remaining: &[usize]) -> usize {
+    (0..remaining.len())
+        .map(|i| {
+            let (a, b, c) = corner(points, remaining, i);
+            (i, cross_2d(a, b, c))
+        })
+        .fold((0, f32::MIN), |best, cur| if cur.1 > best.1 { cur } else { best })
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(points: &[[f32; 3]]) -> Vec<Vec3> {
+        points.iter().map(Vec3::from).collect()
+    }
+
+    fn flat(points: &[[f32; 2]]) -> Vec<Vec3> {
+        points.iter().map(|&[x, y]| vec3!(x, y, 0.0)).collect()
+    }
+
+    fn inside(p: [f32; 2], polygon: &[Vec3]) -> bool {
+        let mut inside = false;
+        for (i, a) in polygon.iter().enumerate() {
+            let b = polygon[(i + 1) % polygon.len()];
+            if (a.y > p[1]) != (b.y > p[1]) && p[0] < a.x + (p[1] - a.y) / (b.y - a.y) * (b.x - a.x)
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    // the polygon is split into n - 2 triangles with its winding, whose areas add up to its
+    // own, so that they cover it without overlapping
+    fn assert_covers(polygon: &[Vec3]) -> Vec<[usize; 3]> {
+        let triangles = triangulate(polygon);
+        assert_eq!(triangles.len(), polygon.len() - 2);
+
+        let normal = newell_normal(polygon);
+        let area = normal.length() / 2.0;
+        let normal = normal.normalize();
+        let mut sum = 0.0;
+        for &[a, b, c] in triangles.iter() {
+            let [a, b, c] = [polygon[a], polygon[b], polygon[c]];
+            let triangle_area = Vec3::dot(&Vec3::cross(&(b - a), &(c - a)), &normal) / 2.0;
+            assert!(
+                triangle_area >= 0.0,
+                "{triangles:?} is not wound as {polygon:?}"
+            );
+            sum += triangle_area;
+        }
+        assert!((sum - area).abs() < 1e-4, "{sum} instead of {area}");
+        triangles
+    }
+
+    // every triangle of a flat polygon lies inside of it
+    fn assert_inside(polygon: &[Vec3], triangles: &[[usize; 3]]) {
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|i| polygon[i]);
+            let centroid = [(a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0];
+            assert!(inside(centroid, polygon), "{triangle:?} is outside");
+        }
+    }
+
+    #[test]
+    fn convex() {
+        assert!(triangulate(&flat(&[[0.0, 0.0], [1.0, 0.0]])).is_empty());
+        assert_eq!(
+            triangulate(&flat(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])),
+            [[0, 1, 2]]
+        );
+        let hexagon: Vec<[f32; 2]> = (0..6)
+            .map(|i| {
+                let angle = (i as f32 * 60.0).to_radians();
+                [angle.cos(), angle.sin()]
+            })
+            .collect();
+        assert_covers(&flat(&hexagon));
+    }
+
+    #[test]
+    fn concave() {
+        let l_shape = flat(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]);
+        let triangles = assert_covers(&l_shape);
+        assert_inside(&l_shape, &triangles);
+
+        let star: Vec<[f32; 2]> = (0..10)
+            .map(|i| {
+                let angle = (i as f32 * 36.0).to_radians();
+                let radius = if i % 2 == 0 { 2.0 } else { 0.8 };
+                [radius * angle.cos(), radius * angle.sin()]
+            })
+            .collect();
+        let star = flat(&star);
+        let triangles = assert_covers(&star);
+        assert_inside(&star, &triangles);
+    }
+
+    #[test]
+    fn collinear() {
+        // a square with a vertex in the middle of each side, the flat corners are never
+        // clipped on their own
+        let square = flat(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [2.0, 2.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 1.0],
+        ]);
+        let triangles = assert_covers(&square);
+        assert_inside(&square, &triangles);
+
+        // a line has no plane, it is split into a fan
+        let line = flat(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        assert_eq!(triangulate(&line), [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn non_planar() {
+        let quad = polygon(&[
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.2],
+            [0.0, 1.0, 0.0],
+        ]);
+        assert_covers(&quad);
+
+        // a concave one, on a tilted plane
+        let dart = polygon(&[
+            [0.0, 0.0, 0.0],
+            [2.0, 0.0, 2.0],
+            [1.0, 0.5, 1.1],
+            [2.0, 2.0, 2.0],
+            [0.0, 2.0, 0.0],
+        ]);
+        assert_covers(&dart);
+    }
+
+    #[test]
+    fn clockwise() {
+        let mut l_shape = flat(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]);
+        l_shape.reverse();
+        let triangles = assert_covers(&l_shape);
+        assert_inside(&l_shape, &triangles);
+        // the triangles keep the winding of the polygon
+        assert!(newell_normal(&l_shape).z < 0.0);
+        for [a, b, c] in triangles {
+            let [a, b, c] = [l_shape[a], l_shape[b], l_shape[c]];
+            assert!(Vec3::cross(&(b - a), &(c - a)).z < 0.0);
+        }
+    }
+}
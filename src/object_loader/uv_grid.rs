@@ -0,0 +1,81 @@
+use super::texture::Texture;
+
+const SIZE: usize = 1024;
+const CELLS: usize = 8;
+const CELL: usize = SIZE / CELLS;
+const LINE_WIDTH: usize = 2;
+// the digits are drawn with a font of 3x5 dots, each dot being a square of DOT pixels
+const DOT: usize = 6;
+const FONT: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+impl Texture {
+    // the texture used when none is given: a checkerboard of 8x8 cells numbered from 0, at
+    // the (0, 0) texture coordinates, to 63, in rows going left to right and bottom to top.
+    // The red of the cells grows with u and their green with v, so a flipped or rotated
+    // mapping is seen at once
+    pub fn uv_grid() -> Self {
+        let mut data = vec![0; SIZE * SIZE * 4];
+        for y in 0..SIZE {
+            // the rows of the texture go from the top, where v is 1
+            let row = CELLS - 1 - y / CELL;
+            for x in 0..SIZE {
+                let column = x / CELL;
+                let (cell_x, cell_y) = (x % CELL, y % CELL);
+                let rgba = if cell_x < LINE_WIDTH
+                    || cell_y < LINE_WIDTH
+                    || cell_x >= CELL - LINE_WIDTH
+                    || cell_y >= CELL - LINE_WIDTH
+                {
+                    [20, 20, 20, 255]
+                } else if is_digit_dot(row * CELLS + column, cell_x, cell_y) {
+                    [255, 255, 255, 255]
+                } else {
+                    let shade = if (row + column).is_multiple_of(2) {
+                        1.0
+                    } else {
+                        0.6
+                    };
+                    let channel = |i: usize| (shade * (60 + i * 195 / (CELLS - 1)) as f32) as u8;
+                    [channel(column), channel(row), (shade * 140.0) as u8, 255]
+                };
+                let i = (y * SIZE + x) * 4;
+                data[i..i + 4].copy_from_slice(&rgba);
+            }
+        }
+
+        Texture {
+            width: SIZE as u32,
+            height: SIZE as u32,
+            data,
+        }
+    }
+}
+
+// whether the pixel at `x`, `y` of a cell is a dot of its number, which is centered with a
+// dot of space between the digits
+fn is_digit_dot(number: usize, x: usize, y: usize) -> bool {
+    let digits = [number / 10, number % 10];
+    let (width, height) = (7 * DOT, 5 * DOT);
+    let (left, top) = ((CELL - width) / 2, (CELL - height) / 2);
+    if x < left || y < top || x >= left + width || y >= top + height {
+        return false;
+    }
+    let (dot_x, dot_y) = ((x - left) / DOT, (y - top) / DOT);
+    let (digit, dot_x) = match dot_x {
+        0..=2 => (digits[0], dot_x),
+        4..=6 => (digits[1], dot_x - 4),
+        _ => return false,
+    };
+    FONT[digit][dot_y] & (0b100 >> dot_x) != 0
+}
@@ -15,25 +15,56 @@ layout(set = 0, binding = 0) uniform Data {
     vec3 light_color;
     vec3 ambient_light_color;
     bool texture;
+    vec3 camera_pos;
+    float point_size;
 } uniforms;
 
 layout(set = 0, binding = 1) uniform sampler s;
 layout(set = 0, binding = 2) uniform texture2D tex;
 
+layout(set = 0, binding = 3) uniform MaterialData {
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+    float dissolve;
+    bool specular_highlight;
+} material;
+
 void main() {
     vec3 direction_to_light = uniforms.light_pos - in_pos_world;
     float attenuation = 1.0 / dot(direction_to_light, direction_to_light);
 
     vec3 light_color = uniforms.light_color.xyz;
     vec3 ambient_light = uniforms.ambient_light_color.xyz;
-    vec3 diffuse_light = light_color * max(dot(normalize(in_normal_world), normalize(direction_to_light)), 0);
+    vec3 normal = normalize(in_normal_world);
+    vec3 diffuse_light = light_color * max(dot(normal, normalize(direction_to_light)), 0);
 
     vec3 color;
+    float alpha = material.dissolve;
     if (uniforms.texture) {
-        color = texture(sampler2D(tex, s), in_tex_coords).xyz;
+        vec4 texel = texture(sampler2D(tex, s), in_tex_coords);
+        color = texel.rgb;
+        alpha *= texel.a;
     } else {
         color = in_color;
     }
+    color *= material.diffuse;
+
+    // points without a normal can't be lit, they keep their own color
+    if (in_normal_world == vec3(0.0)) {
+        out_color = vec4(color, alpha);
+        return;
+    }
+
+    // most exporters write Ka 0 0 0, in which case the ambient light uses the diffuse color
+    vec3 ambient = any(greaterThan(material.ambient, vec3(0.0))) ? material.ambient : vec3(1.0);
+
+    vec3 specular_light = vec3(0.0);
+    if (material.specular_highlight) {
+        vec3 half_dir = normalize(normalize(direction_to_light) + normalize(uniforms.camera_pos - in_pos_world));
+        specular_light = light_color * material.specular * pow(max(dot(normal, half_dir), 0), max(material.shininess, 1.0));
+    }
 
-    out_color = vec4((diffuse_light + ambient_light) * color, 1.0);
+    out_color = vec4(diffuse_light * color + ambient_light * ambient * color + specular_light, alpha);
 }
\ No newline at end of file
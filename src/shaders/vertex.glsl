@@ -18,13 +18,17 @@ layout(set = 0, binding = 0) uniform Data {
     vec3 light_color;
     vec3 ambient_light_color;
     bool texture;
+    vec3 camera_pos;
+    float point_size;
 } uniforms;
 
 void main() {
     vec4 position_world = uniforms.world * vec4(in_position, 1.0);
     gl_Position = uniforms.proj * uniforms.view * position_world;
     out_pos_world = position_world.xyz;
-    out_normal_world = normalize(mat3(uniforms.world) * in_normal);
+    // points may have no normal
+    out_normal_world = in_normal == vec3(0.0) ? vec3(0.0) : normalize(mat3(uniforms.world) * in_normal);
     out_color = in_color;
     out_tex_coords = in_texture;
+    gl_PointSize = uniforms.point_size;
 }
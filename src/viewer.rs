@@ -0,0 +1,139 @@
+use crate::{
+    graphics::{App, Camera, Settings},
+    object_loader::{
+        cache, error::Warnings, loader, object::ParseOptions, texture::Texture, Object,
+    },
+};
+use std::{error::Error, path::Path};
+use winit::event_loop::EventLoop;
+
+// an object and how it is seen, shown in a window or rendered to images:
+//
+//     let mut viewer = Viewer::load(Path::new("teapot.obj"))?;
+//     viewer.load_texture(Path::new("brick.png"))?;
+//     viewer.set_camera(Camera::orbit(viewer.object().center, 10.0, 0.0, 0.5));
+//     viewer.render(640, 480)?.write_ppm(&mut File::create("teapot.ppm")?)?;
+pub struct Viewer {
+    object: Object,
+    // the grid of numbered cells is shown without a texture
+    texture: Option<Texture>,
+    settings: Settings,
+    // the one framing the object by default
+    camera: Option<Camera>,
+}
+
+impl Viewer {
+    pub fn new(object: Object) -> Self {
+        Self {
+            object,
+            texture: None,
+            settings: Settings::default(),
+            camera: None,
+        }
+    }
+
+    // parses the object, or reads it from the cache when it was parsed before, and loads its
+    // materials. The warnings of the parsing are printed in both cases
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::load_with_options(path, &ParseOptions::default(), |_, _| {})
+    }
+
+    // `load`, parsing the object with `options`. `progress` is regularly called with the
+    // number of bytes parsed so far and the size of the file by the loaders streaming it, the
+    // warnings are printed after its last call
+    pub fn load_with_options(
+        path: &Path,
+        options: &ParseOptions,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<Self, Box<dyn Error>> {
+        let loader = loader::mesh_loader(path);
+        let hash = cache::hash_source(path, &loader.dependencies(path), options)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let cache = cache::cache_path(hash);
+
+        let (mut obj, warnings) = match cache
+            .as_ref()
+            .and_then(|path| Object::read_cache(path, hash).ok())
+        {
+            Some(cached) => cached,
+            None => {
+                let (obj, warnings) = loader.load(path, options, &mut progress).map_err(|e| {
+                    format!(
+                        "failed to parse the {} file {}: {e}",
+                        loader.name(),
+                        path.display()
+                    )
+                })?;
+                let warnings = Warnings::new(&warnings);
+                if let Some(path) = &cache {
+                    if let Err(e) = obj.write_cache(path, hash, &warnings) {
+                        println!("cache warning: failed to write {}: {e}", path.display());
+                    }
+                }
+                (obj, warnings)
+            }
+        };
+        warnings.print(path);
+        loader.load_materials(&mut obj, path);
+        Ok(Self::new(obj))
+    }
+
+    pub fn object(&self) -> &Object {
+        &self.object
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    pub fn set_texture(&mut self, texture: Option<Texture>) {
+        self.texture = texture;
+    }
+
+    pub fn load_texture(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let texture = loader::load_image(path)
+            .map_err(|e| format!("failed to load the texture {}: {e}", path.display()))?;
+        self.texture = Some(texture);
+        Ok(())
+    }
+
+    // where the window starts and the images are rendered from
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = Some(camera);
+    }
+
+    // shows the object in a window until it is closed
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        let event_loop =
+            EventLoop::new().map_err(|e| format!("failed to create the event loop: {e}"))?;
+        let mut app = self.app(Some(&event_loop))?;
+        event_loop.run_app(&mut app)?;
+        Ok(())
+    }
+
+    // renders a `width`x`height` image with the light at the camera, textured when there is a
+    // texture
+    pub fn render(&self, width: u32, height: u32) -> Result<Texture, Box<dyn Error>> {
+        let app = self.app(None)?;
+        app.render(width, height, self.texture.is_some())
+    }
+
+    fn app(&self, event_loop: Option<&EventLoop<()>>) -> Result<App, Box<dyn Error>> {
+        let texture = self.texture.clone().unwrap_or_else(Texture::uv_grid);
+        let mut app = App::new(
+            event_loop,
+            self.object.clone(),
+            texture,
+            self.settings.clone(),
+        )
+        .map_err(|e| format!("failed to initialize vulkan: {e}"))?;
+        if let Some(camera) = &self.camera {
+            app.camera = camera.clone();
+        }
+        Ok(app)
+    }
+}